@@ -1,22 +1,31 @@
 use clap::{CommandFactory, Parser, Subcommand, ValueHint};
 use env_logger::Env;
 use log::Level;
+use output::OutputFormat;
 use resolve_path::PathResolveExt;
 use sm_pkg::{
-    BoxResult, DEFAULT_ROOT, VERSION, fsutil,
+    BoxResult, DEFAULT_ROOT, LOCK_FILE, PROJECT_FILE, STATE_DIR, VERSION, check, generations,
+    lock::{LockedPlugin, Lockfile},
+    manifest::Manifest,
     plugins::{self, create_build_root},
-    project,
+    project::{self, Game, InitOptions},
+    prompt::{NoPrompt, Prompter},
     repo::{
         self, PluginDefinitionProvider,
         git::{DEFAULT_BRANCH, DEFAULT_REPO_URL, Git},
+        search::SearchFilter,
     },
+    schema,
     sdk::{self, Branch, Runtime},
+    stage::Stage,
+    upgrade::{self, ConfigUpgrade},
+    verify,
 };
+use std::{io::Write, process::ExitCode};
 use std::{
-    io,
+    io::{self, IsTerminal},
     path::{Path, PathBuf},
 };
-use std::{io::Write, process::ExitCode};
 
 #[cfg(feature = "repo")]
 use std::fs::{self, File};
@@ -24,6 +33,9 @@ use std::fs::{self, File};
 #[macro_use]
 extern crate log;
 
+mod output;
+mod prompt;
+
 #[derive(Parser, Debug)]
 #[command(name = "completion-derive")]
 struct Cli {
@@ -33,6 +45,10 @@ struct Cli {
     #[arg(long = "generate", value_enum)]
     generator: Option<clap_complete::Shell>,
 
+    /// Format of command results, logs always go to stderr
+    #[arg(short, long, global = true, value_enum, default_value_t)]
+    output: OutputFormat,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -45,11 +61,51 @@ enum Commands {
     Init {
         #[arg(short, long, default_value = ".", value_hint = ValueHint::DirPath)]
         project_root: PathBuf,
+
+        #[arg(short, long, value_enum, value_hint = ValueHint::Other)]
+        game: Option<Game>,
+
+        #[arg(short, long, value_enum, value_hint = ValueHint::Other)]
+        branch: Option<Branch>,
+
+        /// Plugin to add to the new project, may be given more than once
+        #[arg(long = "plugin", value_hint = ValueHint::Unknown)]
+        plugins: Vec<String>,
+
+        /// Use defaults for anything not given on the command line instead of prompting
+        #[arg(short, long)]
+        yes: bool,
     },
     #[command(about = "Install all project dependencies")]
     Install {
         #[arg(short, long, default_value = ".", value_hint = ValueHint::DirPath)]
         project_root: PathBuf,
+
+        /// Reproduce exactly what is recorded in sm-pkg.lock, failing if anything differs
+        #[arg(long)]
+        locked: bool,
+
+        /// Overwrite generated files even if they were edited by hand
+        #[arg(long)]
+        force: bool,
+    },
+    #[command(about = "Show plugins and runtimes with newer versions available")]
+    Outdated {
+        #[arg(short, long, default_value = ".", value_hint = ValueHint::DirPath)]
+        project_root: PathBuf,
+    },
+    #[command(about = "Upgrade plugins, sourcemod or metamod and reinstall")]
+    Upgrade {
+        #[arg(short, long, default_value = ".", value_hint = ValueHint::DirPath)]
+        project_root: PathBuf,
+
+        /// Plugins to upgrade, or sourcemod / metamod. Everything when none are given
+        #[arg(value_hint = ValueHint::Unknown)]
+        plugins: Vec<String>,
+
+        /// Overwrite generated files even if they were edited by hand
+        #[arg(long)]
+        force: bool,
     },
     #[command(about = "Add one or more plugins to a project")]
     Add {
@@ -66,14 +122,76 @@ enum Commands {
 
         #[arg(required = true, value_hint = ValueHint::Unknown)]
         plugins: Vec<String>,
+
+        /// Also remove any declared plugins which depend on the removed plugins
+        #[arg(long)]
+        cascade: bool,
+    },
+
+    #[command(about = "Remove dependencies that are no longer required by any plugin")]
+    Prune {
+        #[arg(short, long, default_value = ".", value_hint = ValueHint::DirPath)]
+        project_root: PathBuf,
     },
 
     #[command(about = "Generate configuration files")]
     Config {
         #[arg(short, long, default_value = ".", value_hint = ValueHint::DirPath)]
         project_root: PathBuf,
+
+        /// Overwrite generated files even if they were edited by hand
+        #[arg(long)]
+        force: bool,
+
+        /// Show local edits made to generated files instead of writing them
+        #[arg(long, conflicts_with = "force")]
+        diff: bool,
+    },
+
+    #[command(about = "Print the project definition")]
+    Show {
+        #[arg(short, long, default_value = ".", value_hint = ValueHint::DirPath)]
+        project_root: PathBuf,
+
+        /// Print the effective definition with every extended file merged in
+        #[arg(long)]
+        resolved: bool,
     },
 
+    #[command(about = "Validate the project definition")]
+    Check {
+        #[arg(short, long, default_value = ".", value_hint = ValueHint::DirPath)]
+        project_root: PathBuf,
+    },
+
+    #[command(about = "Check the game directory for changes made outside of sm-pkg")]
+    Verify {
+        #[arg(short, long, default_value = ".", value_hint = ValueHint::DirPath)]
+        project_root: PathBuf,
+    },
+
+    #[command(about = "List the generations created by install")]
+    Generations {
+        #[arg(short, long, default_value = ".", value_hint = ValueHint::DirPath)]
+        project_root: PathBuf,
+    },
+
+    #[command(about = "Restore the files of an earlier install generation")]
+    Rollback {
+        #[arg(short, long, default_value = ".", value_hint = ValueHint::DirPath)]
+        project_root: PathBuf,
+
+        /// Generation to restore, defaults to the one before the current generation
+        generation: Option<u32>,
+
+        /// Overwrite generated files even if they were edited by hand
+        #[arg(long)]
+        force: bool,
+    },
+
+    #[command(about = "Print the JSON schema for sm-pkg.yaml")]
+    Schema,
+
     #[command(about = "List configured project pacakges")]
     List {
         #[arg(short, long, default_value = ".", value_hint = ValueHint::DirPath)]
@@ -81,8 +199,46 @@ enum Commands {
     },
     #[command(about = "Search package cache", arg_required_else_help = true)]
     Search {
+        /// Words to look for in plugin names, tags and descriptions, misspellings allowed
+        #[arg(value_hint = ValueHint::Unknown)]
+        query: Option<String>,
+
+        /// Only show plugins that support this game
+        #[arg(short, long, value_enum)]
+        game: Option<Game>,
+
+        /// Only show plugins by this author
+        #[arg(long)]
+        author: Option<String>,
+
+        /// Only show plugins with this license
+        #[arg(long)]
+        license: Option<String>,
+
+        /// Only show plugins with this tag
+        #[arg(short, long)]
+        tag: Option<String>,
+
+        /// Only show plugins depending on this plugin
+        #[arg(long)]
+        depends_on: Option<String>,
+
+        /// Also search the plugin overlays of this project
+        #[arg(short, long, value_hint = ValueHint::DirPath)]
+        project_root: Option<PathBuf>,
+    },
+
+    #[command(
+        about = "Show everything known about a plugin",
+        arg_required_else_help = true
+    )]
+    Info {
         #[arg(required = true, value_hint = ValueHint::Unknown)]
-        query: String,
+        plugin: String,
+
+        /// Project to report the plugin's status in
+        #[arg(short, long, default_value = ".", value_hint = ValueHint::DirPath)]
+        project_root: PathBuf,
     },
 
     #[command(about = "Build one or more plugins", arg_required_else_help = true)]
@@ -95,10 +251,18 @@ enum Commands {
 
         #[arg(short('r'), long, value_hint = ValueHint::DirPath)]
         build_root: Option<PathBuf>,
+
+        /// Also use the plugin overlays of this project
+        #[arg(short, long, value_hint = ValueHint::DirPath)]
+        project_root: Option<PathBuf>,
     },
 
     #[command(about = "Update package cache")]
-    Update {},
+    Update {
+        /// Also update the git overlays of this project
+        #[arg(short, long, value_hint = ValueHint::DirPath)]
+        project_root: Option<PathBuf>,
+    },
 
     #[command(about = "Download and install sourcemod")]
     SDKInstall {
@@ -119,6 +283,10 @@ enum Commands {
 
         #[arg(short, long, default_value_t, value_enum, value_hint = ValueHint::Other)]
         runtime: Runtime,
+
+        /// Show which builds install would change for a project instead
+        #[arg(short, long, value_hint = ValueHint::DirPath, conflicts_with_all = ["branch", "runtime"])]
+        project_root: Option<PathBuf>,
     },
 
     #[cfg(feature = "repo")]
@@ -178,21 +346,82 @@ async fn run() -> BoxResult {
         Commands::SDKInstall { branch, runtime } => {
             sdk_install(&app_root_resolved, &runtime, &branch).await
         }
-        Commands::SDKLatest { branch, runtime } => {
-            sdk_latest(&app_root_resolved, &runtime, &branch).await
+        Commands::SDKLatest {
+            branch,
+            runtime,
+            project_root,
+        } => match project_root {
+            Some(project_root) => sdk_changes(&app_root_resolved, &project_root, args.output).await,
+            None => sdk_latest(&app_root_resolved, &runtime, &branch, args.output).await,
+        },
+        Commands::SDKList {} => sdk_list(&app_root_resolved, args.output).await,
+        Commands::Search {
+            query,
+            game,
+            author,
+            license,
+            tag,
+            depends_on,
+            project_root,
+        } => {
+            let filter = SearchFilter {
+                author,
+                license,
+                game,
+                tag,
+                dependency: depends_on,
+            };
+            search(
+                &app_root_resolved,
+                project_root.as_deref(),
+                query.unwrap_or_default(),
+                &filter,
+                args.output,
+            )
+            .await
+        }
+        Commands::Info {
+            plugin,
+            project_root,
+        } => plugin_info(&app_root_resolved, &project_root, &plugin, args.output).await,
+        Commands::Update { project_root } => {
+            update(&app_root_resolved, project_root.as_deref()).await
         }
-        Commands::SDKList {} => sdk_list(&app_root_resolved).await,
-        Commands::Search { query } => search(&app_root_resolved, query).await,
-        Commands::Update {} => update(&app_root_resolved).await,
-        Commands::Init { project_root } => project_init(&app_root_resolved, &project_root).await,
-        Commands::Config { project_root } => {
-            project_config(&app_root_resolved, &project_root).await
+        Commands::Init {
+            project_root,
+            game,
+            branch,
+            plugins,
+            yes,
+        } => {
+            let options = InitOptions {
+                game,
+                branch,
+                plugins,
+                yes,
+            };
+            project_init(&app_root_resolved, &project_root, &options).await
         }
+        Commands::Config {
+            project_root,
+            force,
+            diff,
+        } => project_config(&app_root_resolved, &project_root, force, diff).await,
         Commands::Build {
             plugins,
             branch,
             build_root,
-        } => plugin_build(&app_root_resolved, &plugins, &branch, build_root).await,
+            project_root,
+        } => {
+            plugin_build(
+                &app_root_resolved,
+                &plugins,
+                &branch,
+                build_root,
+                project_root,
+            )
+            .await
+        }
         Commands::Add {
             plugins,
             project_root,
@@ -200,10 +429,59 @@ async fn run() -> BoxResult {
         Commands::Remove {
             plugins,
             project_root,
-        } => package_remove(&app_root_resolved, &project_root, plugins).await,
-        Commands::List { project_root } => package_list(&app_root_resolved, &project_root).await,
-        Commands::Install { project_root } => {
-            package_install(&app_root_resolved, &project_root).await
+            cascade,
+        } => package_remove(&app_root_resolved, &project_root, plugins, cascade).await,
+        Commands::Prune { project_root } => package_prune(&app_root_resolved, &project_root).await,
+        Commands::Show {
+            project_root,
+            resolved,
+        } => project_show(&app_root_resolved, &project_root, resolved).await,
+        Commands::Check { project_root } => project_check(&app_root_resolved, &project_root).await,
+        Commands::Verify { project_root } => {
+            project_verify(&app_root_resolved, &project_root).await
+        }
+        Commands::Generations { project_root } => project_generations(&project_root),
+        Commands::Rollback {
+            project_root,
+            generation,
+            force,
+        } => project_rollback(&app_root_resolved, &project_root, generation, force).await,
+        Commands::Schema => {
+            print!("{}", schema::package_schema_json()?);
+            Ok(())
+        }
+        Commands::List { project_root } => {
+            package_list(&app_root_resolved, &project_root, args.output).await
+        }
+        Commands::Install {
+            project_root,
+            locked,
+            force,
+        } => {
+            let hold = match locked {
+                true => Hold::Everything,
+                false => Hold::Nothing,
+            };
+            let report = package_install(&app_root_resolved, &project_root, hold, force).await?;
+            // Progress has been logged along the way, text output needs nothing more.
+            args.output.emit(&report, |_| ())
+        }
+        Commands::Outdated { project_root } => {
+            package_outdated(&app_root_resolved, &project_root, args.output).await
+        }
+        Commands::Upgrade {
+            project_root,
+            plugins,
+            force,
+        } => {
+            package_upgrade(
+                &app_root_resolved,
+                &project_root,
+                &plugins,
+                force,
+                args.output,
+            )
+            .await
         }
         #[cfg(feature = "repo")]
         Commands::BuildIndex {} => build_index().await,
@@ -224,6 +502,7 @@ async fn plugin_build(
     plugins: &[String],
     branch: &Branch,
     build_root_option: Option<PathBuf>,
+    project_root: Option<PathBuf>,
 ) -> BoxResult {
     let build_root = match build_root_option {
         Some(build_root) => build_root,
@@ -231,9 +510,10 @@ async fn plugin_build(
     };
 
     let sdk_manager = sdk::Manager::new(app_root);
-    let repo = repo::LocalRepo::new(app_root);
+    let repo = repo::open(app_root, project_root.as_deref())?;
     let sdk_env = sdk_manager.get_sdk_env(branch)?;
-    match plugins::build(app_root, &sdk_env, &build_root, &repo, plugins) {
+    let resolved = plugins::resolve(&repo, plugins)?;
+    match plugins::build(&sdk_env, &build_root, &resolved) {
         Err(e) => return Err(format!("❌ Failed to build plugins: {}", e).into()),
         Ok(_) => {
             error!("✅ Plugins built successfully: {}", build_root.display());
@@ -244,45 +524,59 @@ async fn plugin_build(
 }
 
 async fn plugin_add(app_root: &Path, project_root: &Path, plugins: Vec<String>) -> BoxResult {
-    let repo = repo::LocalRepo::new(app_root);
+    let repo = repo::open(app_root, Some(project_root))?;
     let mut project_manager = project::Project::new(&project_root, &repo)?;
-    project_manager.open_or_new()?;
+    project_manager.open_or_new(&InitOptions::default(), prompter().as_ref())?;
 
     for plugin in plugins {
-        project_manager.add_plugin(repo.find_plugin_definition(&plugin)?)?;
+        project_manager.add_plugin(&plugin)?;
     }
 
     project_manager.save_package_config()
 }
 
-async fn package_list(app_root: &Path, project_root: &Path) -> BoxResult {
-    let repo = repo::LocalRepo::new(app_root);
+async fn package_list(app_root: &Path, project_root: &Path, output: OutputFormat) -> BoxResult {
+    let repo = repo::open(app_root, Some(project_root))?;
     let mut pm = project::Project::new(&project_root, &repo)?;
     pm.open()?;
-    match pm.package {
+    let list = match pm.package {
         None => return Err("❗ No package config found".into()),
-        Some(config) => {
-            if config.plugins.is_empty() {
-                error!("No plugins added");
-            } else {
-                info!("Plugins added to package:");
-                for plugin in config.plugins {
+        Some(config) => output::PackageList {
+            plugins: config.plugins,
+            dependencies: config.dependencies,
+        },
+    };
+    output.emit(&list, |list| {
+        if list.plugins.is_empty() {
+            error!("No plugins added");
+        } else {
+            info!("Plugins added to package:");
+            for plugin in &list.plugins {
+                info!("- {}", plugin);
+            }
+            if !list.dependencies.is_empty() {
+                info!("Dependencies:");
+                for plugin in &list.dependencies {
                     info!("- {}", plugin);
                 }
             }
         }
-    }
-    Ok(())
+    })
 }
 
-async fn package_remove(app_root: &Path, project_root: &Path, plugins: Vec<String>) -> BoxResult {
-    let repo = repo::LocalRepo::new(app_root);
+async fn package_remove(
+    app_root: &Path,
+    project_root: &Path,
+    plugins: Vec<String>,
+    cascade: bool,
+) -> BoxResult {
+    let repo = repo::open(app_root, Some(project_root))?;
     let mut project_manager = project::Project::new(&project_root, &repo)?;
     project_manager.open()?;
 
     plugins.iter().try_for_each(|p| -> BoxResult {
         let def = repo.find_plugin_definition(p)?;
-        project_manager.remove_plugin(def)?;
+        project_manager.remove_plugin(def, cascade)?;
         Ok(())
     })?;
 
@@ -296,66 +590,861 @@ async fn package_remove(app_root: &Path, project_root: &Path, plugins: Vec<Strin
     Ok(())
 }
 
-async fn project_init(app_root: &Path, project_root: &Path) -> BoxResult {
-    let repo = repo::LocalRepo::new(app_root);
+async fn package_prune(app_root: &Path, project_root: &Path) -> BoxResult {
+    let repo = repo::open(app_root, Some(project_root))?;
+    let mut project_manager = project::Project::new(&project_root, &repo)?;
+    project_manager.open()?;
+
+    let removed = project_manager.prune_dependencies()?;
+    if removed.is_empty() {
+        info!("No unused dependencies found");
+        return Ok(());
+    }
+    for plugin in &removed {
+        info!("Pruned {}", plugin);
+    }
+
+    project_manager.save_package_config()
+}
+
+async fn project_init(app_root: &Path, project_root: &Path, options: &InitOptions) -> BoxResult {
+    let repo = repo::open(app_root, Some(project_root))?;
+    let mut project_manager = project::Project::new(&project_root, &repo)?;
+    project_manager.open_or_new(options, prompter().as_ref())
+}
+
+/// Prompt on the terminal when there is one, otherwise fail any question that needs an answer.
+fn prompter() -> Box<dyn Prompter> {
+    if io::stdin().is_terminal() {
+        Box::new(prompt::InquirePrompter)
+    } else {
+        Box::new(NoPrompt)
+    }
+}
+
+async fn project_config(
+    app_root: &Path,
+    project_root: &Path,
+    force: bool,
+    diff: bool,
+) -> BoxResult {
+    let repo = repo::open(app_root, Some(project_root))?;
+    check::ensure_valid(project_root, &repo)?;
     let mut project_manager = project::Project::new(&project_root, &repo)?;
-    project_manager.open_or_new()
+    project_manager.open()?;
+    if diff {
+        let diffs = project_manager.config_diffs()?;
+        if diffs.is_empty() {
+            info!("No generated files have local edits");
+        }
+        for diff in diffs {
+            print!("{}", diff);
+        }
+        return Ok(());
+    }
+    project_manager.write_configs(force)
 }
 
-async fn project_config(app_root: &Path, project_root: &Path) -> BoxResult {
-    let repo = repo::LocalRepo::new(app_root);
+async fn project_show(app_root: &Path, project_root: &Path, resolved: bool) -> BoxResult {
+    if !resolved {
+        print!(
+            "{}",
+            std::fs::read_to_string(project_root.join(PROJECT_FILE))?
+        );
+        return Ok(());
+    }
+    let repo = repo::open(app_root, Some(project_root))?;
     let mut project_manager = project::Project::new(&project_root, &repo)?;
     project_manager.open()?;
-    project_manager.write_configs()
+    let mut package = project_manager.package.expect("No package found?");
+    package.extends = None;
+    print!("{}", serde_yaml::to_string(&package)?);
+    Ok(())
+}
+
+async fn project_check(app_root: &Path, project_root: &Path) -> BoxResult {
+    let repo = repo::open(app_root, Some(project_root))?;
+    check::ensure_valid(project_root, &repo)?;
+    info!("{} is valid", PROJECT_FILE);
+    Ok(())
 }
 
-async fn package_install(app_root: &Path, project_root: &Path) -> BoxResult {
-    let repo = repo::LocalRepo::new(app_root);
+async fn project_verify(app_root: &Path, project_root: &Path) -> BoxResult {
+    let repo = repo::open(app_root, Some(project_root))?;
     let mut project_manager = project::Project::new(&project_root, &repo)?;
     project_manager.open()?;
 
     let project_config = project_manager.package.as_ref().expect("No package found?");
+    let manifest = Manifest::open(project_root)?;
+    if manifest.files.is_empty() && manifest.generated.is_empty() {
+        return Err("❗ Nothing has been installed yet, run install first".into());
+    }
+
+    let drift = verify::verify(project_root, project_config.mod_folder(), &manifest)?;
+    if drift.is_empty() {
+        info!("No changes detected");
+        return Ok(());
+    }
+    for file in &drift {
+        match &file.owner {
+            Some(owner) => warn!("{}: {} ({})", file.kind, file.path.display(), owner),
+            None => warn!("{}: {}", file.kind, file.path.display()),
+        }
+    }
+
+    Err(format!("❗ {} files differ from what sm-pkg installed", drift.len()).into())
+}
+
+/// Print a table of declared plugins, and sourcemod / metamod, that have a newer version than
+/// the one locked.
+async fn package_outdated(app_root: &Path, project_root: &Path, output: OutputFormat) -> BoxResult {
+    let repo = repo::open(app_root, Some(project_root))?;
+    let mut project_manager = project::Project::new(&project_root, &repo)?;
+    project_manager.open()?;
+    let package = match &project_manager.package {
+        Some(package) => package,
+        None => return Err("❗ No package config found".into()),
+    };
+    let lock = Lockfile::open(project_root)?.unwrap_or_default();
+    let installed = Installed::open(project_root, &lock)?;
+
+    let mut outdated = output::Outdated {
+        outdated: Vec::new(),
+    };
+    let mut specs = package.declared_plugins();
+    // Dependencies of dependencies are only recorded in the lockfile.
+    for locked in &lock.plugins {
+        if !package.declared_names().contains(&locked.name.as_str()) {
+            specs.push(locked.name.clone());
+        }
+    }
+    for spec in &specs {
+        let spec = plugins::PluginSpec::parse(spec)?;
+        let current = installed.plugin(spec.name);
+        let locked = lock.find_plugin(spec.name).map(|p| p.version.clone());
+        let latest = repo
+            .find_plugin_definition(spec.name)
+            .ok()
+            .map(|definition| definition.version);
+        if latest.is_some() && locked == latest && current == latest {
+            continue;
+        }
+        outdated.outdated.push(output::OutdatedEntry {
+            name: spec.name.to_string(),
+            kind: "plugin",
+            required: spec
+                .requirement
+                .as_ref()
+                .map_or("*".to_string(), |r| r.to_string()),
+            current,
+            locked,
+            allowed: match (&spec.requirement, &latest) {
+                (Some(requirement), Some(latest)) => requirement.matches(latest),
+                _ => true,
+            },
+            latest,
+        });
+    }
+
+    let manager = sdk::Manager::new(app_root);
+    for runtime in [Runtime::Sourcemod, Runtime::Metamod] {
+        let requirement = package.build_requirement(&runtime)?;
+        let current = installed
+            .build(&runtime)
+            .map(|archive| build_name(&archive));
+        let locked = lock.build(&runtime).map(|archive| build_name(archive));
+        let latest = match manager.fetch_latest_build(&runtime, &package.branch).await {
+            Ok(archive) => build_name(&archive),
+            Err(e) => {
+                warn!("Cannot fetch the latest {} build: {}", runtime, e);
+                continue;
+            }
+        };
+        if locked.as_ref() == Some(&latest) && current.as_ref() == Some(&latest) {
+            continue;
+        }
+        outdated.outdated.push(output::OutdatedEntry {
+            name: runtime.to_string(),
+            kind: "runtime",
+            allowed: match &requirement {
+                Some(requirement) => {
+                    sdk::Build::parse(&latest).is_ok_and(|build| requirement.matches(&build))
+                }
+                None => true,
+            },
+            required: requirement.map_or(package.branch.to_string(), |r| r.to_string()),
+            current,
+            locked,
+            latest: Some(latest),
+        });
+    }
+
+    output.emit(&outdated, |outdated| {
+        if outdated.outdated.is_empty() {
+            info!("Everything is up to date");
+            return;
+        }
+        let mut rows =
+            vec![["Name", "Required", "Current", "Locked", "Latest"].map(|h| h.to_string())];
+        for entry in &outdated.outdated {
+            let mut latest = entry.latest.clone().unwrap_or("not in index".to_string());
+            if !entry.allowed {
+                latest.push_str(" (excluded by requirement)");
+            }
+            rows.push([
+                entry.name.clone(),
+                entry.required.clone(),
+                entry.current.clone().unwrap_or("-".to_string()),
+                entry.locked.clone().unwrap_or("-".to_string()),
+                latest,
+            ]);
+        }
+        let widths: Vec<usize> = (0..5)
+            .map(|i| rows.iter().map(|r| r[i].len()).max().unwrap_or(0))
+            .collect();
+        for row in rows {
+            info!(
+                "{:<w0$}  {:<w1$}  {:<w2$}  {:<w3$}  {}",
+                row[0],
+                row[1],
+                row[2],
+                row[3],
+                row[4],
+                w0 = widths[0],
+                w1 = widths[1],
+                w2 = widths[2],
+                w3 = widths[3]
+            );
+        }
+    })
+}
+
+/// Build number of an archive name for display, falling back to the name itself.
+/// Versions actually in place in the game directory, which may lag behind sm-pkg.lock when it
+/// was updated elsewhere. Anything without files in the install manifest is not installed.
+struct Installed<'l> {
+    manifest: Manifest,
+    /// Summary of the current generation, what the last install or rollback put in place.
+    generation: Option<generations::Generation>,
+    /// Used for installs made before generations existed.
+    lock: &'l Lockfile,
+}
+
+impl<'l> Installed<'l> {
+    fn open(project_root: &Path, lock: &'l Lockfile) -> BoxResult<Self> {
+        let current = generations::current(project_root)?;
+        Ok(Installed {
+            manifest: Manifest::open(project_root)?,
+            generation: generations::list(project_root)?
+                .into_iter()
+                .find(|g| Some(g.number) == current),
+            lock,
+        })
+    }
+
+    fn has_files(&self, owner: &str) -> bool {
+        self.manifest.files.iter().any(|f| f.plugin == owner)
+    }
+
+    fn plugin(&self, name: &str) -> Option<String> {
+        if !self.has_files(name) {
+            return None;
+        }
+        match &self.generation {
+            Some(generation) => generation.plugins.iter().find_map(|p| {
+                p.split_once('@')
+                    .filter(|(n, _)| *n == name)
+                    .map(|(_, version)| version.to_string())
+            }),
+            None => self.lock.find_plugin(name).map(|p| p.version.clone()),
+        }
+    }
+
+    /// Archive name of the installed `runtime` build.
+    fn build(&self, runtime: &Runtime) -> Option<String> {
+        if !self.has_files(&runtime.to_string()) {
+            return None;
+        }
+        match &self.generation {
+            Some(generation) => match runtime {
+                Runtime::Sourcemod => generation.sourcemod.clone(),
+                Runtime::Metamod => generation.metamod.clone(),
+            },
+            None => self.lock.build(runtime).cloned(),
+        }
+    }
+}
+
+fn build_name(archive: &str) -> String {
+    sdk::Build::parse(archive).map_or(archive.to_string(), |b| b.to_string())
+}
+
+/// What an install has to keep as recorded in sm-pkg.lock.
+#[derive(Clone, Copy)]
+enum Hold<'a> {
+    /// Resolve everything afresh.
+    Nothing,
+    /// Reproduce the lockfile exactly.
+    Everything,
+    /// Keep everything as locked apart from the named plugins, or sourcemod and metamod.
+    AllExcept(&'a [String]),
+}
+
+impl Hold<'_> {
+    fn holds(&self, name: &str) -> bool {
+        match self {
+            Hold::Nothing => false,
+            Hold::Everything => true,
+            Hold::AllExcept(names) => !names.iter().any(|n| n == name),
+        }
+    }
+}
+
+/// Move `plugins`, or everything when empty, to their latest versions and reinstall. Version
+/// requirements excluding the latest version are updated in sm-pkg.yaml, which is put back as
+/// it was if the install fails.
+async fn package_upgrade(
+    app_root: &Path,
+    project_root: &Path,
+    plugins: &[String],
+    force: bool,
+    output: OutputFormat,
+) -> BoxResult {
+    repo::fetch_overlays(app_root, Some(project_root))?;
+    let repo = repo::open(app_root, Some(project_root))?;
+    let mut project_manager = project::Project::new(&project_root, &repo)?;
+    project_manager.open()?;
+    let original = std::fs::read(project_root.join(PROJECT_FILE))?;
+    let bumped = project_manager.bump_requirements(plugins)?;
+    if !bumped.is_empty() {
+        project_manager.save_package_config()?;
+    }
+
+    let hold = match plugins.is_empty() {
+        true => Hold::Nothing,
+        false => Hold::AllExcept(plugins),
+    };
+    let mut report = match package_install(app_root, project_root, hold, force).await {
+        Ok(report) => report,
+        Err(e) => {
+            if !bumped.is_empty() {
+                std::fs::write(project_root.join(PROJECT_FILE), original)?;
+            }
+            return Err(e);
+        }
+    };
+    for (from, to) in bumped {
+        info!("Updated {} to {} in {}", from, to, PROJECT_FILE);
+        report
+            .requirements
+            .push(output::RequirementChange { from, to });
+    }
+    // Progress has been logged along the way, text output needs nothing more.
+    output.emit(&report, |_| ())
+}
+
+async fn package_install(
+    app_root: &Path,
+    project_root: &Path,
+    hold: Hold<'_>,
+    force: bool,
+) -> BoxResult<output::InstallReport> {
+    repo::fetch_overlays(app_root, Some(project_root))?;
+    let repo = repo::open(app_root, Some(project_root))?;
+    check::ensure_valid(project_root, &repo)?;
+    let mut project_manager = project::Project::new(&project_root, &repo)?;
+    project_manager.open()?;
+    // Refuse up front rather than after everything has been built and installed.
+    project_manager.check_config_edits(force)?;
+
+    let project_config = project_manager.package.as_ref().expect("No package found?");
+    let lock = match Lockfile::open(project_root)? {
+        Some(lock) => Some(lock),
+        None if !matches!(hold, Hold::Nothing) => {
+            return Err(format!("❗ No {} found", LOCK_FILE).into());
+        }
+        None => None,
+    };
+
+    let resolved = plugins::resolve(&repo, &project_config.declared_plugins())?;
+    let selected = match hold {
+        Hold::AllExcept(names) => names,
+        _ => &[],
+    };
+    for name in selected {
+        let runtime = [Runtime::Sourcemod, Runtime::Metamod]
+            .iter()
+            .any(|r| r.to_string() == *name);
+        if !runtime && !resolved.iter().any(|p| p.name == *name) {
+            return Err(format!("❗ {} is not a plugin of this project", name).into());
+        }
+    }
+    // Dependencies are not covered by check, catch them before spending time compiling.
+    plugins::check_games(&resolved, project_config.game)?;
+    plugins::verify_extensions(&resolved)?;
+    let mut locked_plugins = Vec::new();
+    for plugin in &resolved {
+        locked_plugins.push(LockedPlugin::new(
+            plugin,
+            repo.plugin_revision(&plugin.name),
+        )?);
+    }
+    if let Some(lock) = &lock {
+        match hold {
+            Hold::Nothing => {}
+            Hold::Everything => lock.verify(&locked_plugins)?,
+            Hold::AllExcept(_) => {
+                let held: Vec<LockedPlugin> = locked_plugins
+                    .iter()
+                    .filter(|p| hold.holds(&p.name))
+                    .cloned()
+                    .collect();
+                lock.verify_plugins(&held)
+                    .map_err(|e| format!("{}, include it in the upgrade", e))?;
+            }
+        }
+    }
+
     let sdk_manager = sdk::Manager::new(app_root);
+    let mut builds = Vec::new();
+    for runtime in [Runtime::Metamod, Runtime::Sourcemod] {
+        let requirement = project_config.build_requirement(&runtime)?;
+        let archive = match &lock {
+            Some(lock) if hold.holds(&runtime.to_string()) => match lock.build(&runtime) {
+                Some(archive) => archive.clone(),
+                None => {
+                    return Err(format!("❗ No {} build recorded in {}", runtime, LOCK_FILE).into());
+                }
+            },
+            _ => {
+                sdk_manager
+                    .resolve_build(&runtime, &project_config.branch, requirement.as_ref())
+                    .await?
+            }
+        };
+        if let Some(requirement) = &requirement
+            && !requirement.matches(&sdk::Build::parse(&archive)?)
+        {
+            return Err(format!(
+                "❗ Locked {} {} does not satisfy {}, run `sm-pkg upgrade {}` to update it",
+                runtime, archive, requirement, runtime
+            )
+            .into());
+        }
+        builds.push(archive);
+    }
+    let [metamod, sourcemod] = <[String; 2]>::try_from(builds).expect("one build per runtime");
+
     let build_root = create_build_root(app_root)?;
     let outputs = plugins::build(
-        app_root,
         &sdk_manager.get_sdk_env(&project_config.branch)?,
         &build_root,
-        &repo::LocalRepo::new(app_root),
-        &project_config.plugins,
+        &resolved,
     )?;
 
-    let mod_folder = project_root.join(project_config.game.mod_folder());
+    // Work out everything that gets installed before touching the game directory.
+    let mut runtime_files = Vec::new();
+    for (plugin, build_dir) in resolved.iter().zip(&outputs) {
+        runtime_files.push(plugins::runtime_files(plugin, build_dir)?);
+    }
+
+    let mod_folder = project_root.join(project_config.mod_folder());
     if !mod_folder.exists() {
         return Err(format!("Mod folder does not exist: {}", mod_folder.display()).into());
     }
-    sdk_manager
-        .install_metamod(&project_config.branch, &mod_folder)
-        .await?;
-    sdk_manager
-        .install_sourcemod(&project_config.branch, &mod_folder)
-        .await?;
 
-    let sm_root = mod_folder.join("addons").join("sourcemod");
+    // Everything is put together in a staging directory first and only swapped into the game
+    // directory once it is complete, so a failure never leaves the server half upgraded.
+    let stage = Stage::new(project_root)?;
+    let staged_mod_folder = stage.root().join(project_config.mod_folder());
+    std::fs::create_dir_all(&staged_mod_folder)?;
+    let previous = Manifest::open(project_root)?;
+    let mut manifest = Manifest::default();
+    let mut stock_configs = Vec::new();
+    let runtimes = [
+        (Runtime::Metamod, &metamod),
+        (Runtime::Sourcemod, &sourcemod),
+    ];
+    for (runtime, archive) in runtimes {
+        let files = match runtime {
+            Runtime::Metamod => {
+                sdk_manager
+                    .install_metamod_build(archive, &staged_mod_folder)
+                    .await?
+            }
+            Runtime::Sourcemod => {
+                sdk_manager
+                    .install_sourcemod_build(archive, &staged_mod_folder)
+                    .await?
+            }
+        };
+        for file in files {
+            let path = project_config.mod_folder().join(&file);
+            manifest.record(
+                stage.root(),
+                &runtime.to_string(),
+                &stage.root().join(&path),
+            )?;
+            if upgrade::is_config(&file) {
+                stock_configs.push(path);
+            }
+        }
+    }
 
-    for build_root in outputs {
-        info!("Installing {:?} -> {:?}", &build_root, &sm_root);
-        fsutil::copy_dir_all(&build_root, &sm_root)?;
+    let sm_root = staged_mod_folder.join("addons").join("sourcemod");
+
+    for (plugin, files) in resolved.iter().zip(runtime_files) {
+        info!("Installing {}", plugin.name);
+        for (src, dest) in files {
+            manifest.install_file(stage.root(), &plugin.name, &src, &sm_root.join(dest))?;
+        }
+        for installed in plugins::install_extensions(plugin, &sm_root)? {
+            manifest.record(stage.root(), &plugin.name, &installed)?;
+        }
     }
 
-    project_manager.write_configs()
-}
+    project_manager.stage_configs(stage.root(), &mut manifest)?;
+    // Keep local edits to stock sourcemod and metamod configs that are not generated by us.
+    let base =
+        upgrade::previous_stock_configs(&sdk_manager, lock.as_ref(), project_config.mod_folder())
+            .await;
+    let generated: Vec<PathBuf> = manifest.generated.iter().map(|f| f.path.clone()).collect();
+    let upgraded = upgrade::reconcile_configs(
+        project_root,
+        stage.root(),
+        &stock_configs,
+        &previous,
+        &base,
+        &generated,
+    )?;
+    for (path, outcome) in &upgraded {
+        if *outcome != ConfigUpgrade::Replaced {
+            manifest.record_edited(stage.root(), path)?;
+        }
+    }
+    let stale = manifest.stale(&previous);
+    manifest.save(stage.root())?;
+    Lockfile {
+        sourcemod: Some(sourcemod.clone()),
+        metamod: Some(metamod.clone()),
+        plugins: locked_plugins.clone(),
+    }
+    .save(stage.root())?;
 
-async fn search(root_path: &Path, query: String) -> BoxResult {
-    let repo = repo::LocalRepo::new(root_path);
-    let matches: Vec<plugins::Definition> = repo.search(&query)?;
-    matches
+    stage.commit(&stale)?;
+    // Copies of generated files under the state directory are not worth reporting.
+    let removed: Vec<PathBuf> = stale
         .into_iter()
-        .for_each(|p| info!("{} - {} - {}", p.name, p.version, p.description));
+        .filter(|path| !path.starts_with(STATE_DIR))
+        .collect();
+    for path in &removed {
+        info!("Removed {}", path.display());
+    }
+    for (path, outcome) in &upgraded {
+        match outcome {
+            ConfigUpgrade::Replaced => debug!("Updated {}", path.display()),
+            ConfigUpgrade::Kept => info!("Kept local edits to {}", path.display()),
+            ConfigUpgrade::Merged => info!("Merged upstream changes into {}", path.display()),
+            ConfigUpgrade::Skipped => warn!(
+                "Skipped {}, it has local edits and the new stock version could not be merged",
+                path.display()
+            ),
+        }
+    }
+    info!("Wrote {}", LOCK_FILE);
+    // The install is already in place, a missing snapshot only means it cannot be rolled back to.
+    let generation = match generations::create(project_root) {
+        Ok(generation) => {
+            info!("Created generation {}", generation.number);
+            Some(generation.number)
+        }
+        Err(e) => {
+            warn!("Failed to create a generation for this install: {}", e);
+            None
+        }
+    };
+
+    Ok(output::InstallReport {
+        sourcemod,
+        metamod,
+        plugins: locked_plugins,
+        removed,
+        configs: upgraded
+            .into_iter()
+            .filter(|(_, outcome)| *outcome != ConfigUpgrade::Replaced)
+            .map(|(path, outcome)| output::ConfigChange { path, outcome })
+            .collect(),
+        generation,
+        requirements: Vec::new(),
+    })
+}
+
+fn project_generations(project_root: &Path) -> BoxResult {
+    let current = generations::current(project_root)?;
+    let generations = generations::list(project_root)?;
+    if generations.is_empty() {
+        info!("No generations yet, they are created by install");
+    }
+    for generation in generations {
+        let marker = match current == Some(generation.number) {
+            true => " (current)",
+            false => "",
+        };
+        info!(
+            "{:>4}  {}  {}, {} plugins{}",
+            generation.number,
+            generation.created,
+            generation.sourcemod.as_deref().unwrap_or("no sourcemod"),
+            generation.plugins.len(),
+            marker
+        );
+    }
     Ok(())
 }
 
-async fn update(root_path: &Path) -> BoxResult {
+async fn project_rollback(
+    app_root: &Path,
+    project_root: &Path,
+    generation: Option<u32>,
+    force: bool,
+) -> BoxResult {
+    let repo = repo::open(app_root, Some(project_root))?;
+    let mut project_manager = project::Project::new(&project_root, &repo)?;
+    project_manager.open()?;
+    project_manager.check_config_edits(force)?;
+
+    let current = generations::current(project_root)?;
+    let number = match (generation, current) {
+        (Some(number), _) => number,
+        (None, Some(current)) => match generations::list(project_root)?
+            .iter()
+            .rev()
+            .find(|g| g.number < current)
+        {
+            Some(previous) => previous.number,
+            None => {
+                return Err(format!("❗ No generation before {} to roll back to", current).into());
+            }
+        },
+        (None, None) => return Err("❗ No generations yet, they are created by install".into()),
+    };
+    if current == Some(number) {
+        return Err(format!("❗ Generation {} is already current", number).into());
+    }
+
+    let generation = generations::restore(project_root, number)?;
+    info!(
+        "Rolled back to generation {} from {}",
+        generation.number, generation.created
+    );
+    Ok(())
+}
+
+async fn search(
+    root_path: &Path,
+    project_root: Option<&Path>,
+    query: String,
+    filter: &SearchFilter,
+    output: OutputFormat,
+) -> BoxResult {
+    let repo = repo::open(root_path, project_root)?;
+    let matches: Vec<plugins::Definition> = repo.search(&query, filter)?;
+    let results = output::SearchResults {
+        plugins: matches
+            .into_iter()
+            .map(|p| output::PluginSummary {
+                name: p.name,
+                version: p.version,
+                description: p.description,
+            })
+            .collect(),
+    };
+    output.emit(&results, |results| {
+        if results.plugins.is_empty() {
+            info!("No plugins found");
+        }
+        for p in &results.plugins {
+            info!("{} - {} - {}", p.name, p.version, p.description);
+        }
+    })
+}
+
+async fn plugin_info(
+    root_path: &Path,
+    project_root: &Path,
+    name: &str,
+    output: OutputFormat,
+) -> BoxResult {
+    let repo = repo::open(root_path, Some(project_root))?;
+    let plugin = repo.find_plugin_definition(name)?;
+    let dependencies = dependency_tree(&repo, &plugin, &mut vec![plugin.name.clone()]);
+    let required_by = repo
+        .dependents(&plugin.name)?
+        .into_iter()
+        .map(|p| p.name)
+        .collect();
+
+    let mut project = None;
+    if project_root.join(PROJECT_FILE).exists() {
+        let mut project_manager = project::Project::new(&project_root, &repo)?;
+        project_manager.open()?;
+        if let Some(package) = &project_manager.package {
+            project = Some(output::ProjectStatus {
+                declared: package
+                    .declared_plugins()
+                    .into_iter()
+                    .find(|spec| plugins::PluginSpec::name_of(spec) == plugin.name),
+                installed: Lockfile::open(project_root)?
+                    .and_then(|lock| lock.find_plugin(&plugin.name).map(|p| p.version.clone())),
+                supported: plugin.supports(package.game),
+            });
+        }
+    }
+
+    let info = output::PluginInfo {
+        plugin,
+        dependencies,
+        required_by,
+        project,
+    };
+    output.emit(&info, log_plugin_info)
+}
+
+fn log_plugin_info(info: &output::PluginInfo) {
+    let plugin = &info.plugin;
+    info!("{} {}", plugin.name, plugin.version);
+    info!("{}", plugin.description);
+    if let Some(url) = &plugin.url {
+        info!("URL: {}", url);
+    }
+    if let Some(authors) = &plugin.authors {
+        info!("Authors: {}", authors.join(", "));
+    }
+    if let Some(license) = &plugin.license {
+        info!("License: {}", license);
+    }
+    match plugin.games {
+        Some(_) => info!("Games: {}", plugin.supported_games()),
+        None => info!("Games: all"),
+    }
+    if let Some(tags) = &plugin.tags
+        && !tags.is_empty()
+    {
+        info!("Tags: {}", tags.join(", "));
+    }
+    let lists = [
+        ("Inputs", &plugin.inputs),
+        ("Gamedata", &plugin.gamedata),
+        ("Translations", &plugin.translations),
+    ];
+    for (title, paths) in lists {
+        if let Some(paths) = paths
+            && !paths.is_empty()
+        {
+            info!("{}:", title);
+            for path in paths {
+                info!("- {}", path.display());
+            }
+        }
+    }
+    if let Some(configs) = &plugin.configs {
+        info!("Configs:");
+        for config in configs {
+            info!("- {}", config.path.display());
+            let mut options: Vec<_> = config.options.iter().collect();
+            options.sort();
+            for (key, value) in options {
+                info!("    {} {}", key, value);
+            }
+        }
+    }
+    if let Some(extensions) = &plugin.extensions {
+        info!("Extensions:");
+        for extension in extensions {
+            let arches: Vec<&str> = [("x86", &extension.x86), ("x64", &extension.x64)]
+                .iter()
+                .filter(|(_, binary)| binary.is_some())
+                .map(|(arch, _)| *arch)
+                .collect();
+            info!("- {} ({})", extension.name, arches.join(", "));
+        }
+    }
+    if !info.dependencies.is_empty() {
+        info!("Dependencies:");
+        log_dependency_tree(&info.dependencies, 0);
+    }
+    if !info.required_by.is_empty() {
+        info!("Required by:");
+        for dependent in &info.required_by {
+            info!("- {}", dependent);
+        }
+    }
+
+    let Some(project) = &info.project else {
+        return;
+    };
+    match (&project.declared, &project.installed) {
+        (Some(spec), _) => info!("Project: declared as {}", spec),
+        (None, Some(_)) => info!("Project: pulled in as a dependency"),
+        (None, None) => info!("Project: not used"),
+    }
+    if let Some(version) = &project.installed {
+        info!("Installed: {}", version);
+    }
+    if !project.supported {
+        warn!("{} does not support the project's game", plugin.name);
+    }
+}
+
+/// The dependencies of `plugin` and theirs below them, `path` holding the plugins above the
+/// current one so cycles are reported instead of followed.
+fn dependency_tree(
+    repo: &dyn PluginDefinitionProvider,
+    plugin: &plugins::Definition,
+    path: &mut Vec<String>,
+) -> Vec<output::DependencyNode> {
+    let mut nodes = Vec::new();
+    for spec in plugin.dependencies.iter().flatten() {
+        let name = plugins::PluginSpec::name_of(spec);
+        let mut node = output::DependencyNode {
+            spec: spec.clone(),
+            version: None,
+            satisfied: false,
+            cycle: path.iter().any(|p| p == name),
+            dependencies: Vec::new(),
+        };
+        if let Ok(dependency) = repo.find_plugin_definition(name) {
+            node.satisfied = plugins::PluginSpec::parse(spec)
+                .is_ok_and(|parsed| parsed.check(&dependency).is_ok());
+            node.version = Some(dependency.version.clone());
+            if !node.cycle {
+                path.push(dependency.name.clone());
+                node.dependencies = dependency_tree(repo, &dependency, path);
+                path.pop();
+            }
+        }
+        nodes.push(node);
+    }
+    nodes
+}
+
+fn log_dependency_tree(nodes: &[output::DependencyNode], depth: usize) {
+    let indent = "  ".repeat(depth);
+    for node in nodes {
+        match &node.version {
+            None => info!("{}- {} (not in index)", indent, node.spec),
+            Some(_) if node.cycle => info!("{}- {} (cycle)", indent, node.spec),
+            Some(version) if !node.satisfied => info!(
+                "{}- {} ({}, does not satisfy the requirement)",
+                indent, node.spec, version
+            ),
+            Some(version) => info!("{}- {} ({})", indent, node.spec, version),
+        }
+        log_dependency_tree(&node.dependencies, depth + 1);
+    }
+}
+
+async fn update(root_path: &Path, project_root: Option<&Path>) -> BoxResult {
     let r = Git::open_or_create(
         root_path.join("repo").as_path(),
         DEFAULT_REPO_URL,
@@ -365,28 +1454,105 @@ async fn update(root_path: &Path) -> BoxResult {
 
     info!("Updated local package cache");
 
-    Ok(())
+    repo::update_overlays(root_path, project_root)
 }
 
-async fn sdk_list(root: &Path) -> BoxResult {
+async fn sdk_list(root: &Path, output: OutputFormat) -> BoxResult {
     let sdk_manager = sdk::Manager::new(root);
-    info!("Currently installed sourcemod SDKs:\n");
-    let sdks = sdk_manager.get_installed_sdks();
-    for sdk in sdks {
-        info!("{}", sdk);
-    }
-    Ok(())
+    let list = output::SdkList {
+        sdks: sdk_manager.get_installed_sdks(),
+    };
+    output.emit(&list, |list| {
+        info!("Currently installed sourcemod SDKs:\n");
+        for sdk in &list.sdks {
+            info!("{}", sdk);
+        }
+    })
 }
 
-async fn sdk_latest(root: &Path, runtime: &Runtime, branch: &Branch) -> BoxResult {
+async fn sdk_latest(
+    root: &Path,
+    runtime: &Runtime,
+    branch: &Branch,
+    output: OutputFormat,
+) -> BoxResult {
     let manager = sdk::Manager::new(root);
-    let version = match runtime {
-        Runtime::Metamod => manager.fetch_latest_metamod_build(branch).await?,
-        Runtime::Sourcemod => manager.fetch_latest_sourcemod_build(branch).await?,
+    let latest = output::LatestBuild {
+        runtime: runtime.to_string(),
+        branch: branch.to_string(),
+        archive: manager.fetch_latest_build(runtime, branch).await?,
     };
 
-    info!("Latest version: {version}");
-    Ok(())
+    output.emit(&latest, |latest| {
+        info!("Latest version: {}", latest.archive)
+    })
+}
+
+/// Compare the builds recorded in the lockfile with what install would fetch now.
+async fn sdk_changes(root: &Path, project_root: &Path, output: OutputFormat) -> BoxResult {
+    let repo = repo::open(root, Some(project_root))?;
+    let mut project_manager = project::Project::new(&project_root, &repo)?;
+    project_manager.open()?;
+    let package = match &project_manager.package {
+        Some(package) => package,
+        None => return Err("❗ No package config found".into()),
+    };
+    let lock = Lockfile::open(project_root)?;
+    let manager = sdk::Manager::new(root);
+    let mut changes = output::BuildChanges {
+        runtimes: Vec::new(),
+    };
+    for runtime in [Runtime::Sourcemod, Runtime::Metamod] {
+        let requirement = package.build_requirement(&runtime)?;
+        let wanted = manager
+            .resolve_build(&runtime, &package.branch, requirement.as_ref())
+            .await?;
+        let latest = match requirement {
+            Some(_) => Some(
+                manager
+                    .fetch_latest_build(&runtime, &package.branch)
+                    .await?,
+            ),
+            None => None,
+        };
+        changes.runtimes.push(output::BuildChange {
+            runtime: runtime.to_string(),
+            installed: lock.as_ref().and_then(|lock| lock.build(&runtime).cloned()),
+            wanted,
+            requirement: requirement.map(|r| r.to_string()),
+            latest,
+        });
+    }
+    output.emit(&changes, |changes| {
+        for change in &changes.runtimes {
+            let mut note = String::new();
+            if let Some(requirement) = &change.requirement {
+                note = format!(" (pinned to {}", requirement);
+                if let Some(latest) = &change.latest
+                    && *latest != change.wanted
+                {
+                    note.push_str(&format!(", latest is {}", latest));
+                }
+                note.push(')');
+            }
+            match &change.installed {
+                Some(installed) if *installed == change.wanted => {
+                    info!(
+                        "{}: {} is up to date{}",
+                        change.runtime, change.wanted, note
+                    )
+                }
+                Some(installed) => info!(
+                    "{}: {} -> {}{}",
+                    change.runtime, installed, change.wanted, note
+                ),
+                None => info!(
+                    "{}: {} will be installed{}",
+                    change.runtime, change.wanted, note
+                ),
+            }
+        }
+    })
 }
 
 async fn sdk_install(root: &Path, runtime: &Runtime, branch: &Branch) -> BoxResult {
@@ -439,13 +1605,15 @@ async fn build_all_plugins(root_path: &Path, branch: &Branch) -> BoxResult {
     let mut errors = 0;
 
     for plugin_def in repo.plugins()? {
-        match plugins::build(
-            root_path,
-            &sdk_env,
-            &build_root,
-            &repo,
-            &vec![plugin_def.name.clone()],
-        ) {
+        let resolved = match plugins::resolve(&repo, std::slice::from_ref(&plugin_def.name)) {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                errors += 1;
+                error!("❌ Failed to resolve plugin: {} - {}", e, &plugin_def.name);
+                continue;
+            }
+        };
+        match plugins::build(&sdk_env, &build_root, &resolved) {
             Err(e) => {
                 errors += 1;
                 error!("❌ Failed to build plugin: {} - {}", e, &plugin_def.name);
@@ -0,0 +1,171 @@
+//! Results of the commands supporting `--output json`. Each is printed on stdout as a single
+//! JSON document, logs always go to stderr. Fields are only ever added, never renamed or
+//! removed, so scripts can rely on them.
+
+use serde::Serialize;
+use sm_pkg::{BoxResult, lock::LockedPlugin, plugins, upgrade::ConfigUpgrade};
+use std::path::PathBuf;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    /// Human readable log lines
+    #[default]
+    Text,
+    /// A JSON document on stdout
+    Json,
+}
+
+impl OutputFormat {
+    /// Print `report` as JSON, or hand it to `text` to be logged for people.
+    pub fn emit<T: Serialize>(self, report: &T, text: impl FnOnce(&T)) -> BoxResult {
+        match self {
+            OutputFormat::Text => text(report),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(report)?),
+        }
+        Ok(())
+    }
+}
+
+/// `list`: plugins declared in sm-pkg.yaml, as written including version requirements.
+#[derive(Serialize)]
+pub struct PackageList {
+    pub plugins: Vec<String>,
+    pub dependencies: Vec<String>,
+}
+
+/// `search`: matching plugins, most relevant first.
+#[derive(Serialize)]
+pub struct SearchResults {
+    pub plugins: Vec<PluginSummary>,
+}
+
+#[derive(Serialize)]
+pub struct PluginSummary {
+    pub name: String,
+    pub version: String,
+    pub description: String,
+}
+
+/// `sdk-list`: sourcemod SDKs installed for compiling plugins, eg: `sourcemod-1.12`.
+#[derive(Serialize)]
+pub struct SdkList {
+    pub sdks: Vec<String>,
+}
+
+/// `sdk-latest`: newest build of a runtime on a branch.
+#[derive(Serialize)]
+pub struct LatestBuild {
+    /// `sourcemod` or `metamod`.
+    pub runtime: String,
+    pub branch: String,
+    /// Archive name, eg: sourcemod-1.12.0-git7210-linux.tar.gz
+    pub archive: String,
+}
+
+/// `sdk-latest --project-root`: builds the next install of the project would use.
+#[derive(Serialize)]
+pub struct BuildChanges {
+    pub runtimes: Vec<BuildChange>,
+}
+
+#[derive(Serialize)]
+pub struct BuildChange {
+    /// `sourcemod` or `metamod`.
+    pub runtime: String,
+    /// Archive recorded in sm-pkg.lock, null when there is no lockfile.
+    pub installed: Option<String>,
+    /// Archive install would use.
+    pub wanted: String,
+    /// `sourcemod_version` or `metamod_version` from sm-pkg.yaml.
+    pub requirement: Option<String>,
+    /// Newest archive on the branch, only looked up when a requirement is set.
+    pub latest: Option<String>,
+}
+
+/// `info`: everything known about a plugin.
+#[derive(Serialize)]
+pub struct PluginInfo {
+    /// The plugin's index entry, the fields of its plugin.yaml.
+    pub plugin: plugins::Definition,
+    pub dependencies: Vec<DependencyNode>,
+    /// Names of indexed plugins directly depending on this one.
+    pub required_by: Vec<String>,
+    /// Status in the project, null outside of one.
+    pub project: Option<ProjectStatus>,
+}
+
+#[derive(Serialize)]
+pub struct DependencyNode {
+    /// Dependency as declared, eg: `base@>=2`.
+    pub spec: String,
+    /// Indexed version, null when the plugin is not in the index.
+    pub version: Option<String>,
+    /// Whether the indexed version satisfies the requirement of `spec`.
+    pub satisfied: bool,
+    /// Set when the plugin already appears above this node, its dependencies are left out.
+    pub cycle: bool,
+    pub dependencies: Vec<DependencyNode>,
+}
+
+#[derive(Serialize)]
+pub struct ProjectStatus {
+    /// Spec from sm-pkg.yaml, null when the plugin is not declared.
+    pub declared: Option<String>,
+    /// Version recorded in sm-pkg.lock, null when not installed.
+    pub installed: Option<String>,
+    /// Whether the plugin supports the project's game.
+    pub supported: bool,
+}
+
+/// `outdated`: plugins and runtimes whose locked version is not the latest.
+#[derive(Serialize)]
+pub struct Outdated {
+    pub outdated: Vec<OutdatedEntry>,
+}
+
+#[derive(Serialize)]
+pub struct OutdatedEntry {
+    pub name: String,
+    /// `plugin` or `runtime`.
+    pub kind: &'static str,
+    /// Declared requirement, `*` for none. The branch for runtimes without a pin.
+    pub required: String,
+    /// Version installed in the game directory, null when not installed.
+    pub current: Option<String>,
+    /// Version in sm-pkg.lock, null when not locked.
+    pub locked: Option<String>,
+    /// Latest version, null for plugins missing from the index.
+    pub latest: Option<String>,
+    /// Whether the requirement accepts `latest`.
+    pub allowed: bool,
+}
+
+/// `install` and `upgrade`: what was installed.
+#[derive(Serialize)]
+pub struct InstallReport {
+    /// Archive names, eg: sourcemod-1.12.0-git7210-linux.tar.gz
+    pub sourcemod: String,
+    pub metamod: String,
+    /// Entries written to sm-pkg.lock, `revision` is left out when unknown.
+    pub plugins: Vec<LockedPlugin>,
+    /// Files removed from the game directory, relative to the project root.
+    pub removed: Vec<PathBuf>,
+    /// Stock config files with local edits, see [`ConfigUpgrade`].
+    pub configs: Vec<ConfigChange>,
+    /// Generation created for the install, null when it could not be created.
+    pub generation: Option<u32>,
+    /// Requirements `upgrade` changed in sm-pkg.yaml.
+    pub requirements: Vec<RequirementChange>,
+}
+
+#[derive(Serialize)]
+pub struct ConfigChange {
+    pub path: PathBuf,
+    pub outcome: ConfigUpgrade,
+}
+
+#[derive(Serialize)]
+pub struct RequirementChange {
+    pub from: String,
+    pub to: String,
+}
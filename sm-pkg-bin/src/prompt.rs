@@ -0,0 +1,12 @@
+use inquire::Select;
+use sm_pkg::{BoxResult, prompt::Prompter};
+
+/// Prompts on the terminal using inquire.
+pub struct InquirePrompter;
+
+impl Prompter for InquirePrompter {
+    fn select(&self, message: &str, options: &[String]) -> BoxResult<usize> {
+        let choice = Select::new(message, options.to_vec()).raw_prompt()?;
+        Ok(choice.index)
+    }
+}
@@ -0,0 +1,564 @@
+use crate::{
+    BoxResult, PROJECT_FILE, layers, plugins::PluginSpec, project::Game,
+    repo::PluginDefinitionProvider, schema, sdk,
+};
+use jsonschema::{JsonType, ValidationError, error::TypeKind, error::ValidationErrorKind};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    fs,
+    path::{Path, PathBuf},
+};
+use yaml_rust2::{
+    Event,
+    parser::{MarkedEventReceiver, Parser},
+    scanner::Marker,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A single problem found in sm-pkg.yaml.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Project file the problem is in, relative to the project root.
+    pub file: PathBuf,
+    /// 1-indexed line of the offending key or value, when it could be located.
+    pub line: Option<usize>,
+    /// 1-indexed column of the offending key or value, when it could be located.
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => {
+                write!(f, "{}:{}:{}: ", self.file.display(), line, column)?
+            }
+            _ => write!(f, "{}: ", self.file.display())?,
+        }
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Validate the project definition in `project_root`, returning every problem found. Structure,
+/// value types and ranges are checked against the generated schema, plugins against `repo`. When
+/// the project extends other files each of them is checked on its own, so problems are reported
+/// against the file they are in, and required keys are checked on the merged result.
+pub fn check(
+    project_root: &Path,
+    repo: &dyn PluginDefinitionProvider,
+) -> BoxResult<Vec<Diagnostic>> {
+    let project_file = project_root.join(PROJECT_FILE);
+    if let Err(e) = serde_yaml::from_str::<serde_yaml::Value>(&fs::read_to_string(&project_file)?) {
+        let location = e.location();
+        return Ok(vec![Diagnostic {
+            severity: Severity::Error,
+            file: PathBuf::from(PROJECT_FILE),
+            line: location.as_ref().map(|l| l.line()),
+            column: location.as_ref().map(|l| l.column()),
+            message: e.to_string(),
+        }]);
+    }
+    let layers = match layers::load(&project_file) {
+        Ok(layers) => layers,
+        Err(e) => {
+            return Ok(vec![Diagnostic {
+                severity: Severity::Error,
+                file: PathBuf::from(PROJECT_FILE),
+                line: None,
+                column: None,
+                message: e.to_string(),
+            }]);
+        }
+    };
+    let merged = serde_json::to_value(layers::merge(&layers))?;
+    let game: Option<Game> = merged
+        .get("game")
+        .and_then(|g| serde_json::from_value(g.clone()).ok());
+
+    let mut checker = Checker {
+        schema: schema::package_schema().to_value(),
+        pass: Pass::Layer,
+        file: PathBuf::new(),
+        positions: Positions::default(),
+        seen: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    for layer in &layers {
+        checker.file = match layer.path.strip_prefix(project_root) {
+            Ok(path) => path.to_path_buf(),
+            Err(_) => layer.path.clone(),
+        };
+        checker.positions = Positions::parse(&layer.source);
+        let instance = serde_json::to_value(&layer.document)?;
+        checker.check_schema(&instance)?;
+        checker.check_plugins(&instance, game, repo);
+        checker.check_builds(&instance);
+    }
+
+    checker.pass = Pass::Merged;
+    checker.file = PathBuf::from(PROJECT_FILE);
+    checker.positions = Positions::default();
+    checker.check_schema(&merged)?;
+
+    let mut diagnostics = checker.diagnostics;
+    diagnostics.sort_by(|a, b| (&a.file, a.line, a.column).cmp(&(&b.file, b.line, b.column)));
+    Ok(diagnostics)
+}
+
+/// Run [`check`], logging each diagnostic, and fail if any of them are errors.
+pub fn ensure_valid(project_root: &Path, repo: &dyn PluginDefinitionProvider) -> BoxResult {
+    let diagnostics = check(project_root, repo)?;
+    let mut errors = 0;
+    for diagnostic in &diagnostics {
+        match diagnostic.severity {
+            Severity::Error => {
+                errors += 1;
+                error!("{}", diagnostic);
+            }
+            Severity::Warning => warn!("{}", diagnostic),
+        }
+    }
+    if errors > 0 {
+        return Err(format!("❗ {} has {} errors", PROJECT_FILE, errors).into());
+    }
+    Ok(())
+}
+
+/// Which problems are being looked for. Individual layers may leave out required keys which are
+/// provided by another layer, so those are only reported once everything has been merged.
+#[derive(PartialEq)]
+enum Pass {
+    Layer,
+    Merged,
+}
+
+struct Checker {
+    schema: Value,
+    pass: Pass,
+    /// File currently being checked, relative to the project root.
+    file: PathBuf,
+    positions: Positions,
+    /// Plugins already seen in this or an earlier layer.
+    seen: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn check_schema(&mut self, instance: &Value) -> BoxResult {
+        let validator = jsonschema::draft7::new(&self.schema).map_err(|e| e.to_string())?;
+        let errors: Vec<ValidationError<'static>> = validator
+            .iter_errors(instance)
+            .map(|e| e.to_owned())
+            .collect();
+        for error in &errors {
+            self.report_validation_error(error);
+        }
+        Ok(())
+    }
+
+    fn report_validation_error(&mut self, error: &ValidationError<'static>) {
+        let path = error.instance_path().as_str();
+        let is_required = matches!(error.kind(), ValidationErrorKind::Required { .. });
+        let is_combinator = matches!(
+            error.kind(),
+            ValidationErrorKind::AnyOf { .. } | ValidationErrorKind::OneOfNotValid { .. }
+        );
+        if !is_combinator && is_required != (self.pass == Pass::Merged) {
+            return;
+        }
+        match error.kind() {
+            // Optional values and documented enums are expressed as anyOf/oneOf, dig into the
+            // branches that were actually meant rather than reporting the combinator.
+            ValidationErrorKind::AnyOf { context }
+            | ValidationErrorKind::OneOfNotValid { context } => {
+                let branches: Vec<&Vec<ValidationError<'static>>> = context
+                    .iter()
+                    .filter(|branch| !branch.iter().all(is_null_mismatch))
+                    .collect();
+                let expected: Vec<String> = branches
+                    .iter()
+                    .filter_map(|branch| match branch.as_slice() {
+                        [e] => match e.kind() {
+                            ValidationErrorKind::Constant { expected_value } => {
+                                Some(expected_value.to_string())
+                            }
+                            _ => None,
+                        },
+                        _ => None,
+                    })
+                    .collect();
+                if !expected.is_empty() && expected.len() == branches.len() {
+                    let message = format!(
+                        "`{}`: expected one of {}",
+                        dotted(path),
+                        expected.join(", ")
+                    );
+                    self.error(path, message);
+                    return;
+                }
+                for error in branches.into_iter().flatten() {
+                    self.report_validation_error(error);
+                }
+            }
+            ValidationErrorKind::AdditionalProperties { unexpected } => {
+                let known = known_keys(&self.schema, &self.schema, &segments(path));
+                for key in unexpected {
+                    let mut message = format!("unknown key `{}`", key);
+                    if let Some(suggestion) = suggest(key, &known) {
+                        message.push_str(&format!(", did you mean `{}`?", suggestion));
+                    }
+                    self.error(&format!("{}/{}", path, escape(key)), message);
+                }
+            }
+            _ if path.is_empty() => self.error(path, error.to_string()),
+            _ => self.error(path, format!("`{}`: {}", dotted(path), error)),
+        }
+    }
+
+    fn check_builds(&mut self, instance: &Value) {
+        for key in ["sourcemod_version", "metamod_version"] {
+            if let Some(Value::String(requirement)) = instance.get(key)
+                && let Err(e) = sdk::BuildRequirement::parse(requirement)
+            {
+                self.error(&format!("/{}", key), format!("`{}`: {}", key, e));
+            }
+        }
+    }
+
+    fn check_plugins(
+        &mut self,
+        instance: &Value,
+        game: Option<Game>,
+        repo: &dyn PluginDefinitionProvider,
+    ) {
+        for list in ["plugins", "dependencies"] {
+            let Some(Value::Array(plugins)) = instance.get(list) else {
+                continue;
+            };
+            for (index, plugin) in plugins.iter().enumerate() {
+                let Some(spec) = plugin.as_str() else {
+                    continue;
+                };
+                let path = format!("/{}/{}", list, index);
+                let spec = match PluginSpec::parse(spec) {
+                    Ok(spec) => spec,
+                    Err(e) => {
+                        self.error(&path, e.to_string());
+                        continue;
+                    }
+                };
+                let name = spec.name;
+                if self.seen.iter().any(|s| s == name) {
+                    self.push(
+                        Severity::Warning,
+                        &path,
+                        format!("plugin `{}` is listed more than once", name),
+                    );
+                    continue;
+                }
+                self.seen.push(name.to_string());
+                let definition = match repo.find_plugin_definition(name) {
+                    Ok(definition) => definition,
+                    Err(e) => {
+                        self.error(
+                            &path,
+                            format!("plugin `{}` is not in the index: {}", name, e),
+                        );
+                        continue;
+                    }
+                };
+                if let Err(e) = spec.check(&definition) {
+                    self.error(&path, e.to_string());
+                }
+                if let Some(game) = game
+                    && !definition.supports(game)
+                {
+                    self.error(
+                        &path,
+                        format!(
+                            "plugin `{}` does not support {:?}, it supports: {}",
+                            name,
+                            game,
+                            definition.supported_games()
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    fn error(&mut self, path: &str, message: String) {
+        self.push(Severity::Error, path, message);
+    }
+
+    fn push(&mut self, severity: Severity, path: &str, message: String) {
+        let marker = self.positions.find(path);
+        self.diagnostics.push(Diagnostic {
+            severity,
+            file: self.file.clone(),
+            line: marker.map(|m| m.line()),
+            // yaml-rust2 columns are 0-indexed, unlike its lines.
+            column: marker.map(|m| m.col() + 1),
+            message,
+        });
+    }
+}
+
+fn is_null_mismatch(error: &ValidationError<'static>) -> bool {
+    matches!(
+        error.kind(),
+        ValidationErrorKind::Type {
+            kind: TypeKind::Single(JsonType::Null)
+        }
+    )
+}
+
+/// Property names allowed by the object schema found at `path` inside `schema`.
+fn known_keys(root: &Value, schema: &Value, path: &[String]) -> Vec<String> {
+    if let Some(Value::String(reference)) = schema.get("$ref")
+        && let Some(target) = reference.strip_prefix('#').and_then(|p| root.pointer(p))
+    {
+        return known_keys(root, target, path);
+    }
+    for combinator in ["allOf", "anyOf", "oneOf"] {
+        if let Some(Value::Array(options)) = schema.get(combinator) {
+            let keys: Vec<String> = options
+                .iter()
+                .flat_map(|option| known_keys(root, option, path))
+                .collect();
+            if !keys.is_empty() {
+                return keys;
+            }
+        }
+    }
+    match path.split_first() {
+        None => match schema.get("properties") {
+            Some(Value::Object(properties)) => properties.keys().cloned().collect(),
+            _ => Vec::new(),
+        },
+        Some((segment, rest)) => {
+            if let Some(property) = schema.get("properties").and_then(|p| p.get(segment)) {
+                known_keys(root, property, rest)
+            } else if let Some(items) = schema.get("items") {
+                known_keys(root, items, rest)
+            } else {
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// The closest known key to a misspelt one, if any is close enough to be a likely typo.
+fn suggest<'k>(key: &str, known: &'k [String]) -> Option<&'k str> {
+    known
+        .iter()
+        .map(|candidate| (strsim::levenshtein(key, candidate), candidate))
+        .filter(|(distance, _)| *distance <= (key.len() / 3).max(2))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// A JSON pointer as the dotted key path users see in yaml, eg: templates.core_cfg.logging
+fn dotted(pointer: &str) -> String {
+    segments(pointer).join(".")
+}
+
+fn segments(pointer: &str) -> Vec<String> {
+    pointer
+        .split('/')
+        .skip(1)
+        .map(|s| s.replace("~1", "/").replace("~0", "~"))
+        .collect()
+}
+
+/// Source positions of every key and sequence item in a yaml document, keyed by JSON pointer.
+#[derive(Default)]
+struct Positions {
+    markers: HashMap<String, Marker>,
+    stack: Vec<Frame>,
+}
+
+enum Frame {
+    Mapping { path: String, key: Option<String> },
+    Sequence { path: String, index: usize },
+}
+
+impl Positions {
+    fn parse(source: &str) -> Positions {
+        let mut positions = Positions::default();
+        // Syntax errors have already been reported by serde_yaml, whatever was collected
+        // before the error is still useful.
+        let _ = Parser::new_from_str(source).load(&mut positions, false);
+        positions
+    }
+
+    /// Marker for `path`, falling back to the closest parent that has one.
+    fn find(&self, path: &str) -> Option<Marker> {
+        let mut path = path;
+        loop {
+            if let Some(marker) = self.markers.get(path) {
+                return Some(*marker);
+            }
+            path = &path[..path.rfind('/')?];
+        }
+    }
+
+    /// Path of the node that is starting, recording where it is. Returns None for mapping keys.
+    fn node(&mut self, mark: Marker, scalar: Option<&str>) -> Option<String> {
+        match self.stack.last_mut() {
+            None => Some(String::new()),
+            Some(Frame::Mapping { path, key }) => match key.take() {
+                Some(key) => Some(format!("{}/{}", path, escape(&key))),
+                None => {
+                    let key_name = scalar.unwrap_or_default().to_string();
+                    self.markers
+                        .insert(format!("{}/{}", path, escape(&key_name)), mark);
+                    *key = Some(key_name);
+                    None
+                }
+            },
+            Some(Frame::Sequence { path, index }) => {
+                let item = format!("{}/{}", path, index);
+                *index += 1;
+                self.markers.insert(item.clone(), mark);
+                Some(item)
+            }
+        }
+    }
+}
+
+impl MarkedEventReceiver for Positions {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        match ev {
+            Event::Scalar(value, ..) => {
+                self.node(mark, Some(&value));
+            }
+            Event::Alias(_) => {
+                self.node(mark, None);
+            }
+            Event::MappingStart(..) => {
+                let path = self.node(mark, None).unwrap_or_default();
+                self.stack.push(Frame::Mapping { path, key: None });
+            }
+            Event::SequenceStart(..) => {
+                let path = self.node(mark, None).unwrap_or_default();
+                self.stack.push(Frame::Sequence { path, index: 0 });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{TestRepo, write};
+
+    /// Check a project made of `files`, returning each diagnostic as it is printed.
+    fn run(files: &[(&str, &str)]) -> Vec<String> {
+        let root = tempfile::tempdir().unwrap();
+        for (path, contents) in files {
+            write(&root.path().join(path), contents);
+        }
+        let mut repo = TestRepo::new(&[("mge", "1.0.0", &[]), ("css-only", "1.0.0", &[])]);
+        repo.plugins[1].games = Some(vec![Game::CSTRIKE]);
+        check(root.path(), &repo)
+            .unwrap()
+            .iter()
+            .map(|d| d.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn unknown_keys_are_located_with_a_suggestion() {
+        let diagnostics = run(&[(
+            PROJECT_FILE,
+            "game: TF\nbranch: Stable\nplugins: [mge]\ntemplates:\n  sourcemod_cfg:\n    sm_show_activty: 1\n",
+        )]);
+        assert_eq!(
+            diagnostics,
+            vec![
+                "sm-pkg.yaml:6:5: error: unknown key `sm_show_activty`, did you mean `sm_show_activity`?"
+            ]
+        );
+    }
+
+    #[test]
+    fn out_of_range_and_mistyped_values_are_reported() {
+        let diagnostics = run(&[(
+            PROJECT_FILE,
+            "game: TF\nbranch: Stable\nplugins: []\ntemplates:\n  sourcemod_cfg:\n    sm_show_activity: 40\n    sm_flood_time: fast\n",
+        )]);
+        assert_eq!(
+            diagnostics,
+            vec![
+                "sm-pkg.yaml:6:5: error: `templates.sourcemod_cfg.sm_show_activity`: 40 is greater than the maximum of 31",
+                "sm-pkg.yaml:7:5: error: `templates.sourcemod_cfg.sm_flood_time`: \"fast\" is not of types \"null\", \"number\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn required_keys_are_only_checked_once_merged() {
+        let base = (
+            "base.yaml",
+            "templates:\n  sourcemod_cfg:\n    sm_show_activity: 1\n",
+        );
+        assert!(
+            run(&[
+                (
+                    PROJECT_FILE,
+                    "extends: base.yaml\ngame: TF\nbranch: Stable\nplugins: []\n"
+                ),
+                base,
+            ])
+            .is_empty()
+        );
+        assert_eq!(
+            run(&[(PROJECT_FILE, "extends: base.yaml\ngame: TF\n"), base]),
+            vec![
+                "sm-pkg.yaml: error: \"branch\" is a required property",
+                "sm-pkg.yaml: error: \"plugins\" is a required property",
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_and_unsupported_plugins_are_reported() {
+        let diagnostics = run(&[(
+            PROJECT_FILE,
+            "game: TF\nbranch: Stable\nplugins: [mge, missing, css-only]\n",
+        )]);
+        assert_eq!(
+            diagnostics,
+            vec![
+                "sm-pkg.yaml:3:16: error: plugin `missing` is not in the index: Plugin not found: missing",
+                "sm-pkg.yaml:3:25: error: plugin `css-only` does not support TF, it supports: CSTRIKE",
+            ]
+        );
+    }
+}
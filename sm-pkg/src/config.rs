@@ -1,38 +1,26 @@
-use crate::{BoxResult, CONFIG_FILE_PATH, sdk};
-use resolve_path::PathResolveExt;
+use crate::{BoxResult, CONFIG_FILE, repo::overlay::OverlaySource, sdk};
 use serde::{Deserialize, Serialize};
 use serde_yaml;
-use std::{fs::File, path::PathBuf};
+use std::{fs::File, path::Path};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Settings shared by every project, config.yaml in the app root.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Config {
+    #[serde(default)]
     pub compiler_args: sdk::CompilerArgs,
+    /// Plugin sources stacked over the default repository for every project.
+    #[serde(default)]
+    pub overlays: Vec<OverlaySource>,
 }
 
 impl Config {
-    pub fn open_or_default() -> BoxResult<Self> {
-        match config_path() {
-            Some(p) => {
-                let reader = File::open(p)?;
-                let config: Config = serde_yaml::from_reader(reader)?;
-                Ok(config)
-            }
-            None => Ok(Config {
-                compiler_args: sdk::CompilerArgs::default(),
-            }),
+    pub fn open_or_default(app_root: &Path) -> BoxResult<Self> {
+        let path = app_root.join(CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Config::default());
         }
-    }
-}
-
-fn config_path() -> Option<PathBuf> {
-    match PathBuf::from(CONFIG_FILE_PATH).try_resolve() {
-        Ok(path) => {
-            if path.exists() {
-                Some(path.to_path_buf())
-            } else {
-                None
-            }
-        }
-        Err(_) => None,
+        let reader = File::open(path)?;
+        let config: Config = serde_yaml::from_reader(reader)?;
+        Ok(config)
     }
 }
@@ -1,4 +1,5 @@
-use std::path::Path;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
 use std::{fs, io};
 
 pub fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> io::Result<()> {
@@ -14,3 +15,101 @@ pub fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> io::Result<
     }
     Ok(())
 }
+
+/// Recursively list all files under `root`, relative to it, in a stable sorted order.
+pub fn list_files(root: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    list_files_into(root.as_ref(), Path::new(""), &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+fn list_files_into(root: &Path, prefix: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(root.join(prefix))? {
+        let entry = entry?;
+        let rel = prefix.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            list_files_into(root, &rel, files)?;
+        } else {
+            files.push(rel);
+        }
+    }
+    Ok(())
+}
+
+/// Hex encoded sha256 of a single file.
+pub fn hash_file(path: impl AsRef<Path>) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Hex encoded sha256 over every file in a directory tree. Both the relative paths and the
+/// contents are hashed so that renames are detected as well. Each path is NUL terminated and
+/// each file's contents prefixed with their length, so different trees never hash the same.
+pub fn hash_dir(root: impl AsRef<Path>) -> io::Result<String> {
+    let root = root.as_ref();
+    let mut hasher = Sha256::new();
+    for rel in list_files(root)? {
+        let mut file = fs::File::open(root.join(&rel))?;
+        hasher.update(rel.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(file.metadata()?.len().to_le_bytes());
+        io::copy(&mut file, &mut hasher)?;
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Hard link `src` to `dst`, creating any missing parent directories. Falls back to copying when
+/// the two are on different filesystems or linking is not supported.
+pub fn link_or_copy(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> io::Result<()> {
+    let dst = dst.as_ref();
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::hard_link(&src, dst).is_err() {
+        fs::copy(&src, dst)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::write;
+
+    #[test]
+    fn hash_dir_tells_file_boundaries_apart() {
+        let one = tempfile::tempdir().unwrap();
+        write(&one.path().join("a"), "xb\0y");
+        let two = tempfile::tempdir().unwrap();
+        write(&two.path().join("a"), "x");
+        write(&two.path().join("b"), "y");
+        assert_ne!(hash_dir(one.path()).unwrap(), hash_dir(two.path()).unwrap());
+    }
+
+    #[test]
+    fn hash_dir_depends_on_paths_and_contents() {
+        let root = tempfile::tempdir().unwrap();
+        write(
+            &root.path().join("scripting/rip.sp"),
+            "#include <sourcemod>",
+        );
+        let hash = hash_dir(root.path()).unwrap();
+        assert_eq!(hash_dir(root.path()).unwrap(), hash);
+
+        fs::rename(
+            root.path().join("scripting/rip.sp"),
+            root.path().join("scripting/rip2.sp"),
+        )
+        .unwrap();
+        let renamed = hash_dir(root.path()).unwrap();
+        assert_ne!(renamed, hash);
+
+        write(
+            &root.path().join("scripting/rip2.sp"),
+            "#include <sdktools>",
+        );
+        assert_ne!(hash_dir(root.path()).unwrap(), renamed);
+    }
+}
@@ -0,0 +1,111 @@
+use crate::project::Game;
+
+/// Static details about a supported game.
+#[derive(Debug)]
+pub struct GameInfo {
+    pub game: Game,
+    /// Name of the mod folder inside the dedicated server install, eg: tf
+    pub mod_folder: &'static str,
+    pub name: &'static str,
+    /// Map used by the generated startup script when none is configured.
+    pub default_map: &'static str,
+    /// Whether the dedicated server ships a 64-bit srcds_run_64 binary.
+    pub has_64bit: bool,
+    /// Steam app id of the dedicated server, when it is distributed through steamcmd.
+    pub app_id: Option<u32>,
+}
+
+const fn game(
+    game: Game,
+    mod_folder: &'static str,
+    name: &'static str,
+    default_map: &'static str,
+    has_64bit: bool,
+    app_id: Option<u32>,
+) -> GameInfo {
+    GameInfo {
+        game,
+        mod_folder,
+        name,
+        default_map,
+        has_64bit,
+        app_id,
+    }
+}
+
+// https://github.com/alliedmodders/sourcemod/tree/master/gamedata/sdktools.games
+// https://developer.valvesoftware.com/wiki/Dedicated_Servers_List
+#[rustfmt::skip]
+pub static GAMES: &[GameInfo] = &[
+    game(Game::AG2, "ag2", "Adrenaline Gamer 2", "stalkyard", false, None),
+    game(Game::ALIENSWARM, "swarm", "Alien Swarm", "asi-jac1-landingbay_01", false, Some(635)),
+    game(Game::AOC, "ageofchivalry", "Age of Chivalry", "aoc_battlefield", false, Some(17515)),
+    game(Game::BG2, "bg2", "Battle Grounds 2", "bg_townguard", false, None),
+    game(Game::BMS, "bms", "Black Mesa", "dm_bounce", false, Some(346680)),
+    game(Game::CSPROMOD, "cspromod", "CSPromod", "de_dust2", false, None),
+    game(Game::CSTRIKE, "cstrike", "Counter-Strike: Source", "de_dust2", true, Some(232330)),
+    game(Game::DINODDAY, "dinodday", "Dino D-Day", "ddd_hill", false, None),
+    game(Game::DOD, "dod", "Day of Defeat: Source", "dod_anzio", true, Some(232290)),
+    game(Game::DOI, "doi", "Day of Infamy", "bastogne", false, Some(462310)),
+    game(Game::DYSTOPIA, "dystopia", "Dystopia", "dys_broadcast", false, Some(17585)),
+    game(Game::EMPIRES, "empires", "Empires", "emp_canyon", false, Some(460040)),
+    game(Game::ESMOD, "esmod", "Eternal Silence", "esm_alpha", false, None),
+    game(Game::FAS, "fas", "Firearms: Source", "fas_bridge", false, None),
+    game(Game::FF, "FortressForever", "Fortress Forever", "ff_2fort", false, None),
+    game(Game::FOF, "fof", "Fistful of Frags", "fof_fistful", false, Some(295230)),
+    game(Game::GESOURCE, "gesource", "GoldenEye: Source", "ge_archives", false, None),
+    game(Game::GMOD9, "gmod9", "Garry's Mod 9", "gm_construct", false, None),
+    game(Game::HIDDEN, "hidden", "The Hidden: Source", "hdn_origin", false, None),
+    game(Game::HL1MP, "hl1mp", "Half-Life Deathmatch: Source", "crossfire", true, Some(255470)),
+    game(Game::HL2CTF, "hl2ctf", "Half-Life 2: Capture the Flag", "ctf_2fort", false, None),
+    game(Game::HL2MP, "hl2mp", "Half-Life 2: Deathmatch", "dm_lockdown", true, Some(232370)),
+    game(Game::INSURGENCY, "insurgency", "Insurgency", "ministry", false, Some(237410)),
+    game(Game::IOS, "iosoccer", "IOSoccer", "8v8_vienna", false, None),
+    game(Game::KZ, "kz", "Kreedz Climbing", "kz_bhop", false, None),
+    game(Game::LEFT4DEAD2, "left4dead2", "Left 4 Dead 2", "c1m1_hotel", false, Some(222860)),
+    game(Game::MODULARCOMBAT, "modularcombat", "Modular Combat", "mc_forest", false, None),
+    game(Game::NEOTOKYO, "NeotokyoSource", "NEOTOKYO", "nt_dawn_ctg", false, Some(313600)),
+    game(Game::NMRIH, "nmrih", "No More Room in Hell", "nmo_broadway", false, Some(317670)),
+    game(Game::NUCLEARDAWN, "nucleardawn", "Nuclear Dawn", "hydro", false, Some(111710)),
+    game(Game::OBSIDIAN, "obsidian", "Obsidian Conflict", "oc_lobby", false, None),
+    game(Game::OPENFORTRESS, "open_fortress", "Open Fortress", "dm_2fort", false, None),
+    game(Game::PF2, "pf2", "Pre-Fortress 2", "ctf_2fort", false, None),
+    game(Game::PVKILL, "pvkii", "Pirates, Vikings, and Knights II", "bt_island", false, Some(17575)),
+    game(Game::REACTIVEDROP, "reactivedrop", "Alien Swarm: Reactive Drop", "asi-jac1-landingbay_01", false, Some(582400)),
+    game(Game::MKBETA, "mkbeta", "Military Conflict: Vietnam Beta", "mcv_hue", false, None),
+    game(Game::SHIP, "ship", "The Ship", "batavier", false, Some(2403)),
+    game(Game::SOURCEFORTS, "sourceforts", "SourceForts", "sf_skywalk", false, None),
+    game(Game::SYNERGY, "synergy", "Synergy", "d1_trainstation_01", false, Some(17520)),
+    game(Game::TF, "tf", "Team Fortress 2", "pl_badwater", true, Some(232250)),
+    game(Game::TF2CLASSIC, "tf2classic", "Team Fortress 2 Classic", "ctf_2fort", false, None),
+    game(Game::TF2CLASSIFIED, "tf2classified", "Team Fortress 2 Classified", "pl_badwater", false, None),
+    game(Game::TREASON, "treason", "Trouble in Terrorist Town: Source", "ttt_minecraft_b5", false, None),
+    game(Game::ZM, "zombie_master", "Zombie Master", "zm_backwoods", false, None),
+    game(Game::ZPANIC, "zps", "Zombie Panic! Source", "zps_cinema", false, Some(17505)),
+];
+
+/// Look up the registry entry for a game. Every [`Game`] variant has an entry.
+pub fn lookup(game: Game) -> &'static GameInfo {
+    GAMES
+        .iter()
+        .find(|info| info.game == game)
+        .expect("every game has a registry entry")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ValueEnum;
+
+    #[test]
+    fn every_game_has_one_registry_entry() {
+        for game in Game::value_variants() {
+            let entries = GAMES.iter().filter(|info| info.game == *game).count();
+            assert_eq!(entries, 1, "{:?} has {} registry entries", game, entries);
+            assert_eq!(lookup(*game).game, *game);
+            assert!(!game.mod_folder().as_os_str().is_empty(), "{:?}", game);
+            assert!(!game.to_string().is_empty(), "{:?}", game);
+        }
+        assert_eq!(GAMES.len(), Game::value_variants().len());
+    }
+}
@@ -0,0 +1,316 @@
+use crate::{
+    BoxResult, LOCK_FILE, MANIFEST_FILE, PROJECT_FILE, STATE_DIR, fsutil, layers,
+    lock::Lockfile,
+    manifest::{self, Manifest},
+    stage::Stage,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+/// Number of generations kept by [`create`], older ones are pruned.
+pub const KEEP_GENERATIONS: usize = 10;
+
+/// A snapshot of every sm-pkg managed file taken after a successful install, along with the
+/// sm-pkg.yaml, every file it extends and the lockfile that produced it. Each one is stored in
+/// .sm-pkg/generations/<number>, laid out like the project root, with a <number>.yaml summary
+/// next to it. Plugin and extension binaries are hard linked where possible so generations are
+/// cheap to keep, sm-pkg only ever replaces them with renames so the snapshot keeps its own copy.
+/// Everything else, configs in particular, may be edited in place and is copied.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Generation {
+    pub number: u32,
+    /// UTC time the generation was created, eg: 2025-01-31 18:04:12
+    pub created: String,
+    /// Sourcemod archive that was installed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sourcemod: Option<String>,
+    /// Metamod archive that was installed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metamod: Option<String>,
+    /// Installed plugins, eg: class_restrict@1.2.0
+    pub plugins: Vec<String>,
+    /// The project definition with every `extends` layer merged in. Layers outside the project
+    /// root cannot be restored, this records what they contained.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<serde_yaml::Value>,
+}
+
+impl Generation {
+    fn dir(&self, project_root: &Path) -> PathBuf {
+        generations_dir(project_root).join(self.number.to_string())
+    }
+}
+
+/// Every generation of the project, oldest first.
+pub fn list(project_root: &Path) -> BoxResult<Vec<Generation>> {
+    let dir = generations_dir(project_root);
+    let mut generations = Vec::new();
+    if !dir.exists() {
+        return Ok(generations);
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "yaml") {
+            let generation: Generation = serde_yaml::from_reader(File::open(path)?)?;
+            generations.push(generation);
+        }
+    }
+    generations.sort_by_key(|g| g.number);
+    Ok(generations)
+}
+
+/// Number of the generation the project currently matches, if any.
+pub fn current(project_root: &Path) -> BoxResult<Option<u32>> {
+    let path = generations_dir(project_root).join("current");
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(fs::read_to_string(path)?.trim().parse()?))
+}
+
+fn set_current(project_root: &Path, number: u32) -> BoxResult {
+    fs::write(
+        generations_dir(project_root).join("current"),
+        number.to_string(),
+    )?;
+    Ok(())
+}
+
+/// Snapshot the installed project as a new generation and make it the current one.
+pub fn create(project_root: &Path) -> BoxResult<Generation> {
+    let manifest = Manifest::open(project_root)?;
+    let lock = Lockfile::open(project_root)?.unwrap_or_default();
+    let project_file = project_root.join(PROJECT_FILE);
+    let layers = match project_file.exists() {
+        true => layers::load(&project_file)?,
+        false => Vec::new(),
+    };
+    let generation = Generation {
+        number: list(project_root)?.last().map_or(1, |g| g.number + 1),
+        created: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        sourcemod: lock.sourcemod,
+        metamod: lock.metamod,
+        plugins: lock
+            .plugins
+            .iter()
+            .map(|p| format!("{}@{}", p.name, p.version))
+            .collect(),
+        package: match layers.is_empty() {
+            true => None,
+            false => Some(layers::merge(&layers)),
+        },
+    };
+    let dir = generation.dir(project_root);
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    let mut copied: Vec<PathBuf> = vec![
+        PathBuf::from(PROJECT_FILE),
+        PathBuf::from(LOCK_FILE),
+        Path::new(STATE_DIR).join(MANIFEST_FILE),
+    ];
+    let canonical_root = project_root.canonicalize()?;
+    for layer in &layers {
+        if let Ok(path) = layer.path.canonicalize()?.strip_prefix(&canonical_root)
+            && !copied.iter().any(|c| c == path)
+        {
+            copied.push(path.to_path_buf());
+        }
+    }
+    for file in &manifest.generated {
+        copied.push(file.path.clone());
+        copied.push(manifest::generated_copy(&file.path));
+    }
+    for file in &manifest.files {
+        if copied.contains(&file.path) {
+            continue;
+        }
+        if is_binary(&file.path) {
+            fsutil::link_or_copy(project_root.join(&file.path), dir.join(&file.path))?;
+        } else {
+            copied.push(file.path.clone());
+        }
+    }
+    for path in copied {
+        if project_root.join(&path).exists() {
+            copy_file(&project_root.join(&path), &dir.join(&path))?;
+        }
+    }
+    serde_yaml::to_writer(
+        File::create(generations_dir(project_root).join(format!("{}.yaml", generation.number)))?,
+        &generation,
+    )?;
+    set_current(project_root, generation.number)?;
+    prune(project_root, KEEP_GENERATIONS)?;
+    Ok(generation)
+}
+
+/// Remove all but the newest `keep` generations. The current generation is always kept.
+pub fn prune(project_root: &Path, keep: usize) -> BoxResult<Vec<Generation>> {
+    let current = current(project_root)?;
+    let mut generations = list(project_root)?;
+    let excess = generations.len().saturating_sub(keep);
+    generations.truncate(excess);
+    generations.retain(|g| Some(g.number) != current);
+    for generation in &generations {
+        let dir = generation.dir(project_root);
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::remove_file(generations_dir(project_root).join(format!("{}.yaml", generation.number)))?;
+    }
+    Ok(generations)
+}
+
+/// Put the files of generation `number` back in place, without rebuilding anything. Managed
+/// files that are not part of that generation are removed.
+pub fn restore(project_root: &Path, number: u32) -> BoxResult<Generation> {
+    let generation = match list(project_root)?.into_iter().find(|g| g.number == number) {
+        Some(generation) => generation,
+        None => {
+            return Err(format!("❗ No generation {}, see `sm-pkg generations`", number).into());
+        }
+    };
+    let dir = generation.dir(project_root);
+    let installed = Manifest::open(project_root)?;
+    let restored = Manifest::open(&dir)?;
+
+    // Always copied, linking would tie the live files to the snapshot again.
+    let stage = Stage::new(project_root)?;
+    for path in fsutil::list_files(&dir)? {
+        copy_file(&dir.join(&path), &stage.root().join(&path))?;
+    }
+    let mut stale = restored.stale(&installed);
+    stale.extend(
+        installed
+            .generated
+            .iter()
+            .filter(|f| restored.find_generated(&f.path).is_none())
+            .map(|f| f.path.clone()),
+    );
+    stage.commit(&stale)?;
+    set_current(project_root, number)?;
+    Ok(generation)
+}
+
+/// Compiled plugins and extensions, the only files safe to hard link into a generation.
+fn is_binary(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "smx" || ext == "so" || ext == "dll")
+}
+
+fn copy_file(src: &Path, dst: &Path) -> BoxResult {
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(src, dst)?;
+    Ok(())
+}
+
+fn generations_dir(project_root: &Path) -> PathBuf {
+    project_root.join(STATE_DIR).join("generations")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::write;
+
+    /// A project with a stock config that has been replaced by a generated one, and a plugin.
+    fn installed_project() -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        let config = root.path().join("cstrike/cfg/sourcemod/sourcemod.cfg");
+        let plugin = root.path().join("cstrike/addons/sourcemod/plugins/rip.smx");
+        write(&config, "sm_show_activity 13\n");
+        write(&plugin, "compiled");
+        let mut manifest = Manifest::default();
+        manifest.record(root.path(), "sourcemod", &config).unwrap();
+        manifest.record(root.path(), "rip", &plugin).unwrap();
+        manifest.record_generated(root.path(), &config).unwrap();
+        manifest.save(root.path()).unwrap();
+        root
+    }
+
+    #[test]
+    fn create_keeps_generated_configs_intact() {
+        let root = installed_project();
+        let generation = create(root.path()).unwrap();
+        let config = Path::new("cstrike/cfg/sourcemod/sourcemod.cfg");
+        assert_eq!(
+            fs::read_to_string(root.path().join(config)).unwrap(),
+            "sm_show_activity 13\n"
+        );
+
+        // Editing the live file in place must not change the snapshot.
+        fs::write(root.path().join(config), "edited\n").unwrap();
+        let snapshot = generation.dir(root.path()).join(config);
+        assert_eq!(
+            fs::read_to_string(snapshot).unwrap(),
+            "sm_show_activity 13\n"
+        );
+    }
+
+    #[test]
+    fn restore_copies_files_back() {
+        let root = installed_project();
+        let generation = create(root.path()).unwrap();
+        let plugin = Path::new("cstrike/addons/sourcemod/plugins/rip.smx");
+        // Installs replace plugins rather than writing into them.
+        fs::remove_file(root.path().join(plugin)).unwrap();
+        fs::write(root.path().join(plugin), "rebuilt").unwrap();
+
+        restore(root.path(), generation.number).unwrap();
+        assert_eq!(
+            fs::read_to_string(root.path().join(plugin)).unwrap(),
+            "compiled"
+        );
+        fs::write(root.path().join(plugin), "edited").unwrap();
+        let snapshot = generation.dir(root.path()).join(plugin);
+        assert_eq!(fs::read_to_string(snapshot).unwrap(), "compiled");
+    }
+
+    #[test]
+    fn create_prunes_old_generations() {
+        let root = installed_project();
+        for _ in 0..KEEP_GENERATIONS + 2 {
+            create(root.path()).unwrap();
+        }
+        let numbers: Vec<u32> = list(root.path())
+            .unwrap()
+            .iter()
+            .map(|g| g.number)
+            .collect();
+        assert_eq!(numbers, (3..=12).collect::<Vec<u32>>());
+        assert!(!generations_dir(root.path()).join("1").exists());
+    }
+
+    #[test]
+    fn create_snapshots_every_layer() {
+        let root = installed_project();
+        let shared = tempfile::tempdir().unwrap();
+        write(
+            &root.path().join(PROJECT_FILE),
+            &format!(
+                "extends: [base/tf.yaml, {}]\nplugins: [rip]\n",
+                shared.path().join("shared.yaml").display()
+            ),
+        );
+        write(&root.path().join("base/tf.yaml"), "game: TF\n");
+        write(&shared.path().join("shared.yaml"), "branch: Stable\n");
+
+        let generation = create(root.path()).unwrap();
+        let dir = generation.dir(root.path());
+        assert_eq!(
+            fs::read_to_string(dir.join("base/tf.yaml")).unwrap(),
+            "game: TF\n"
+        );
+        assert!(dir.join(PROJECT_FILE).exists());
+        let package = list(root.path()).unwrap().remove(0).package.unwrap();
+        assert_eq!(package["game"], "TF");
+        assert_eq!(package["branch"], "Stable");
+        assert_eq!(package["plugins"][0], "rip");
+    }
+}
@@ -0,0 +1,294 @@
+use crate::BoxResult;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Other project files a project is layered on top of, relative to the file declaring them.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(untagged)]
+pub enum Extends {
+    One(PathBuf),
+    Many(Vec<PathBuf>),
+}
+
+impl Extends {
+    pub fn paths(&self) -> Vec<&Path> {
+        match self {
+            Extends::One(path) => vec![path],
+            Extends::Many(paths) => paths.iter().map(|p| p.as_path()).collect(),
+        }
+    }
+}
+
+/// A single project file taking part in a layered project.
+pub struct Layer {
+    pub path: PathBuf,
+    pub source: String,
+    pub document: Value,
+}
+
+/// Load a project file along with every file it extends, base files first and the file itself
+/// last. A file extended more than once is only loaded the first time it is reached.
+pub fn load(path: &Path) -> BoxResult<Vec<Layer>> {
+    let mut layers = Vec::new();
+    let mut loaded = Vec::new();
+    load_into(path, &mut Vec::new(), &mut loaded, &mut layers)?;
+    Ok(layers)
+}
+
+fn load_into(
+    path: &Path,
+    chain: &mut Vec<PathBuf>,
+    loaded: &mut Vec<PathBuf>,
+    layers: &mut Vec<Layer>,
+) -> BoxResult {
+    let canonical = match path.canonicalize() {
+        Ok(canonical) => canonical,
+        Err(e) => return Err(format!("❗ Failed to open {}: {}", path.display(), e).into()),
+    };
+    if chain.contains(&canonical) {
+        let cycle: Vec<String> = chain
+            .iter()
+            .chain([&canonical])
+            .map(|p| p.display().to_string())
+            .collect();
+        return Err(format!("❗ extends cycle detected: {}", cycle.join(" -> ")).into());
+    }
+    if loaded.contains(&canonical) {
+        return Ok(());
+    }
+
+    let source = fs::read_to_string(path)?;
+    let document: Value = match serde_yaml::from_str(&source) {
+        Ok(document) => document,
+        Err(e) => return Err(format!("❗ Failed to read {}: {}", path.display(), e).into()),
+    };
+    let extends: Option<Extends> = match document.get("extends") {
+        None => None,
+        Some(value) => Some(serde_yaml::from_value(value.clone())?),
+    };
+
+    chain.push(canonical.clone());
+    if let Some(extends) = extends {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        for base in extends.paths() {
+            load_into(&dir.join(base), chain, loaded, layers)?;
+        }
+    }
+    chain.pop();
+
+    loaded.push(canonical);
+    layers.push(Layer {
+        path: path.to_path_buf(),
+        source,
+        document,
+    });
+    Ok(())
+}
+
+/// Deep merge every layer in order. Mappings are merged key by key, lists are appended and any
+/// other value is overridden by later layers. `extends` itself is taken from the last layer only.
+pub fn merge(layers: &[Layer]) -> Value {
+    let mut merged = Value::Mapping(Mapping::new());
+    for (i, layer) in layers.iter().enumerate() {
+        let mut document = layer.document.clone();
+        if i + 1 < layers.len()
+            && let Value::Mapping(mapping) = &mut document
+        {
+            mapping.remove("extends");
+        }
+        merged = merge_value(merged, document);
+    }
+    merged
+}
+
+fn merge_value(base: Value, overlay: Value) -> Value {
+    match (base, overlay) {
+        (Value::Mapping(mut base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                let merged = match base.remove(&key) {
+                    Some(existing) => merge_value(existing, value),
+                    None => value,
+                };
+                base.insert(key, merged);
+            }
+            Value::Mapping(base)
+        }
+        (Value::Sequence(mut base), Value::Sequence(overlay)) => {
+            base.extend(overlay);
+            Value::Sequence(base)
+        }
+        (_, overlay) => overlay,
+    }
+}
+
+/// Reverse of [`merge`] for the last layer: given the effective document, the merged base layers
+/// and the last layer as it was loaded, work out what the last layer must contain so that merging
+/// it onto `base` gives back `effective`. Keys the layer already had are kept even when they
+/// match the base, so saving does not needlessly rewrite the file.
+pub fn strip_base(
+    effective: &Value,
+    base: &Value,
+    local: Option<&Value>,
+) -> BoxResult<Option<Value>> {
+    match (effective, base) {
+        (Value::Mapping(effective), Value::Mapping(base)) => {
+            let mut out = Mapping::new();
+            for (key, value) in effective {
+                let local_value = local.and_then(|l| l.get(key));
+                let stripped = match base.get(key) {
+                    Some(base_value) => strip_base(value, base_value, local_value)?,
+                    None if value.is_null() => None,
+                    None => Some(value.clone()),
+                };
+                match stripped {
+                    Some(stripped) => {
+                        out.insert(key.clone(), stripped);
+                    }
+                    None if local_value.is_some() => {
+                        out.insert(key.clone(), empty_like(value));
+                    }
+                    None => (),
+                }
+            }
+            match out.is_empty() {
+                true => Ok(None),
+                false => Ok(Some(Value::Mapping(out))),
+            }
+        }
+        (Value::Sequence(effective), Value::Sequence(base)) => {
+            let inherited = effective.len() >= base.len()
+                && effective.iter().zip(base).all(|(e, b)| same(e, b));
+            if !inherited {
+                let missing: Vec<String> = base
+                    .iter()
+                    .filter(|b| !effective.iter().any(|e| same(e, b)))
+                    .map(|v| {
+                        serde_yaml::to_string(v)
+                            .unwrap_or_default()
+                            .trim()
+                            .to_string()
+                    })
+                    .collect();
+                return Err(format!(
+                    "❗ Cannot remove {} as it comes from an extended file, remove it there instead",
+                    missing.join(", ")
+                )
+                .into());
+            }
+            match effective.len() == base.len() {
+                true => Ok(None),
+                false => Ok(Some(Value::Sequence(effective[base.len()..].to_vec()))),
+            }
+        }
+        (effective, base) if same(effective, base) => Ok(None),
+        (effective, _) => Ok(Some(effective.clone())),
+    }
+}
+
+/// Compare two values, treating a mapping key set to null the same as a missing key. Unset
+/// options are written out as null when a [`crate::project::Package`] is serialized.
+fn same(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Mapping(a), Value::Mapping(b)) => {
+            let set = |m: &Mapping| m.iter().filter(|(_, v)| !v.is_null()).count();
+            set(a) == set(b)
+                && a.iter()
+                    .filter(|(_, v)| !v.is_null())
+                    .all(|(k, v)| b.get(k).is_some_and(|other| same(v, other)))
+        }
+        (Value::Sequence(a), Value::Sequence(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))
+        }
+        (a, b) => a == b,
+    }
+}
+
+fn empty_like(value: &Value) -> Value {
+    match value {
+        Value::Mapping(_) => Value::Mapping(Mapping::new()),
+        Value::Sequence(_) => Value::Sequence(Vec::new()),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(source: &str) -> Value {
+        serde_yaml::from_str(source).unwrap()
+    }
+
+    fn layer(source: &str) -> Layer {
+        Layer {
+            path: PathBuf::from("sm-pkg.yaml"),
+            source: source.to_string(),
+            document: yaml(source),
+        }
+    }
+
+    #[test]
+    fn merge_overrides_maps_key_by_key() {
+        let base = layer("game: TF\nbranch: Stable\nserver:\n  hostname: base\n  port: 27015\n");
+        let local = layer("extends: base.yaml\nbranch: Dev\nserver:\n  hostname: local\n");
+        assert_eq!(
+            merge(&[base, local]),
+            yaml(
+                "game: TF\nbranch: Dev\nserver:\n  hostname: local\n  port: 27015\n\
+                 extends: base.yaml\n"
+            )
+        );
+    }
+
+    #[test]
+    fn merge_appends_lists_and_drops_base_extends() {
+        let base = layer("extends: common.yaml\nplugins: [rip, mge]\n");
+        let local = layer("plugins: [tf2-comp-fixes]\n");
+        assert_eq!(
+            merge(&[base, local]),
+            yaml("plugins: [rip, mge, tf2-comp-fixes]\n")
+        );
+    }
+
+    #[test]
+    fn strip_base_writes_only_the_delta() {
+        let base = yaml("game: TF\nbranch: Stable\nplugins: [rip]\nserver:\n  port: 27015\n");
+        let local = yaml("extends: base.yaml\nplugins: [mge]\n");
+        let effective = yaml(
+            "extends: base.yaml\ngame: TF\nbranch: Dev\nplugins: [rip, mge, nativevotes]\n\
+             server:\n  port: 27015\n  hostname: local\n",
+        );
+        assert_eq!(
+            strip_base(&effective, &base, Some(&local)).unwrap(),
+            Some(yaml(
+                "extends: base.yaml\nbranch: Dev\nplugins: [mge, nativevotes]\n\
+                 server:\n  hostname: local\n"
+            ))
+        );
+    }
+
+    #[test]
+    fn strip_base_keeps_keys_the_layer_already_had() {
+        let base = yaml("plugins: [rip]\n");
+        let local = yaml("plugins: []\n");
+        let effective = yaml("plugins: [rip]\n");
+        assert_eq!(
+            strip_base(&effective, &base, Some(&local)).unwrap(),
+            Some(yaml("plugins: []\n"))
+        );
+        assert_eq!(strip_base(&effective, &base, None).unwrap(), None);
+    }
+
+    #[test]
+    fn strip_base_refuses_to_remove_inherited_list_entries() {
+        let base = yaml("plugins: [rip, mge]\n");
+        let effective = yaml("plugins: [rip]\n");
+        let err = strip_base(&effective, &base, None).unwrap_err();
+        assert!(err.to_string().contains("mge"), "{}", err);
+    }
+}
@@ -1,22 +1,43 @@
 #[macro_use]
 extern crate log;
 
+pub mod check;
 pub mod config;
 pub mod fsutil;
+pub mod games;
+pub mod generations;
+pub mod layers;
+pub mod lock;
+pub mod manifest;
 pub mod plugins;
 pub mod project;
+pub mod prompt;
 pub mod repo;
+pub mod schema;
 pub mod sdk;
+pub mod stage;
 pub mod templates;
-const CONFIG_FILE_PATH: &str = "~/.sm-pkg/config.yaml";
+pub mod upgrade;
+pub mod verify;
+/// Global settings, relative to the app root.
+const CONFIG_FILE: &str = "config.yaml";
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const DEFAULT_ROOT: &str = "~/.sm-pkg";
 pub const PROJECT_FILE: &str = "sm-pkg.yaml";
+pub const LOCK_FILE: &str = "sm-pkg.lock";
+/// Directory inside a project where sm-pkg keeps its install state.
+pub const STATE_DIR: &str = ".sm-pkg";
+pub const MANIFEST_FILE: &str = "manifest.yaml";
 pub const DL_CACHE: &str = "dl_cache";
 pub const REPO_URL: &str = "https://github.com/sm-pkg/plugins/archive/refs/heads/master.zip";
 pub const UPDATE_URL: &str =
     "https://raw.githubusercontent.com/sm-pkg/plugins/refs/heads/master/index.yaml";
+pub const SCHEMA_URL: &str =
+    "https://raw.githubusercontent.com/sm-pkg/sm-pkg/refs/heads/master/schema/sm-pkg.json";
 pub const INDEX_FILE: &str = "index.yaml";
 pub const PLUGIN_DEFINITION_FILE: &str = "plugin.yaml";
 
 pub type BoxResult<T = ()> = Result<T, Box<dyn std::error::Error>>;
+
+#[cfg(test)]
+mod testutil;
@@ -0,0 +1,204 @@
+use crate::{BoxResult, LOCK_FILE, fsutil, plugins, sdk::Runtime};
+use serde::{Deserialize, Serialize};
+use std::{fs::File, io::Write as _, path::Path};
+
+/// Lockfile recording the exact inputs used to produce a server install, sm-pkg.lock.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Lockfile {
+    /// Sourcemod archive name that was installed, eg: sourcemod-1.12.0-git7210-linux.tar.gz
+    pub sourcemod: Option<String>,
+    /// Metamod archive name that was installed, eg: mmsource-1.12.0-git1219-linux.tar.gz
+    pub metamod: Option<String>,
+    pub plugins: Vec<LockedPlugin>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LockedPlugin {
+    pub name: String,
+    pub version: String,
+    /// Commit of the plugin repository the sources were taken from, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+    /// sha256 over the plugins source tree.
+    pub source_hash: String,
+}
+
+impl LockedPlugin {
+    pub fn new(plugin: &plugins::Definition, revision: Option<String>) -> BoxResult<Self> {
+        Ok(LockedPlugin {
+            name: plugin.name.clone(),
+            version: plugin.version.clone(),
+            revision,
+            source_hash: fsutil::hash_dir(plugin.source_root()?)?,
+        })
+    }
+}
+
+impl Lockfile {
+    /// Open the lockfile in the project root, if one exists.
+    pub fn open(project_root: &Path) -> BoxResult<Option<Self>> {
+        let path = project_root.join(LOCK_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let lock: Lockfile = serde_yaml::from_reader(File::open(path)?)?;
+        Ok(Some(lock))
+    }
+
+    pub fn save(&self, project_root: &Path) -> BoxResult {
+        let mut file = File::create(project_root.join(LOCK_FILE))?;
+        writeln!(file, "# Generated by sm-pkg, do not edit manually")?;
+        writeln!(file, "---")?;
+        serde_yaml::to_writer(file, self)?;
+        Ok(())
+    }
+
+    /// Archive name recorded for `runtime`.
+    pub fn build(&self, runtime: &Runtime) -> Option<&String> {
+        match runtime {
+            Runtime::Sourcemod => self.sourcemod.as_ref(),
+            Runtime::Metamod => self.metamod.as_ref(),
+        }
+    }
+
+    pub fn find_plugin(&self, name: &str) -> Option<&LockedPlugin> {
+        self.plugins.iter().find(|p| p.name == name)
+    }
+
+    /// Ensure the currently resolved plugins match exactly what was locked.
+    pub fn verify(&self, resolved: &[LockedPlugin]) -> BoxResult {
+        self.verify_plugins(resolved)?;
+
+        if let Some(extra) = self
+            .plugins
+            .iter()
+            .find(|l| !resolved.iter().any(|p| p.name == l.name))
+        {
+            return Err(format!(
+                "❗ Plugin {} is locked but no longer declared, run install without --locked",
+                extra.name
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Ensure each of `plugins` matches what was locked, without requiring every locked plugin
+    /// to be present.
+    pub fn verify_plugins(&self, plugins: &[LockedPlugin]) -> BoxResult {
+        for plugin in plugins {
+            let locked = match self.find_plugin(&plugin.name) {
+                Some(locked) => locked,
+                None => {
+                    return Err(format!("❗ Plugin {} is not in {}", plugin.name, LOCK_FILE).into());
+                }
+            };
+            if locked.version != plugin.version {
+                return Err(format!(
+                    "❗ Plugin {} is locked to version {} but the index provides {}",
+                    plugin.name, locked.version, plugin.version
+                )
+                .into());
+            }
+            if locked.source_hash != plugin.source_hash {
+                return Err(format!(
+                    "❗ Plugin {} sources have changed since they were locked (revision {})",
+                    plugin.name,
+                    locked.revision.as_deref().unwrap_or("unknown")
+                )
+                .into());
+            }
+            if locked.revision != plugin.revision {
+                warn!(
+                    "Plugin {} was locked at revision {}, repo is now at {}",
+                    plugin.name,
+                    locked.revision.as_deref().unwrap_or("unknown"),
+                    plugin.revision.as_deref().unwrap_or("unknown")
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locked(name: &str, version: &str, source_hash: &str) -> LockedPlugin {
+        LockedPlugin {
+            name: name.to_string(),
+            version: version.to_string(),
+            revision: Some("abc123".to_string()),
+            source_hash: source_hash.to_string(),
+        }
+    }
+
+    fn lockfile() -> Lockfile {
+        Lockfile {
+            plugins: vec![
+                locked("rip", "1.3.0", "aaaa"),
+                locked("mge", "2.0.0", "bbbb"),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn verify_accepts_matching_plugins() {
+        let resolved = [
+            locked("mge", "2.0.0", "bbbb"),
+            locked("rip", "1.3.0", "aaaa"),
+        ];
+        lockfile().verify(&resolved).unwrap();
+    }
+
+    #[test]
+    fn verify_rejects_version_mismatch() {
+        let resolved = [
+            locked("rip", "1.4.0", "aaaa"),
+            locked("mge", "2.0.0", "bbbb"),
+        ];
+        let err = lockfile().verify(&resolved).unwrap_err().to_string();
+        assert!(
+            err.contains("locked to version 1.3.0 but the index provides 1.4.0"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn verify_rejects_changed_sources() {
+        let resolved = [
+            locked("rip", "1.3.0", "cccc"),
+            locked("mge", "2.0.0", "bbbb"),
+        ];
+        let err = lockfile().verify(&resolved).unwrap_err().to_string();
+        assert!(err.contains("rip sources have changed"), "{}", err);
+    }
+
+    #[test]
+    fn verify_rejects_plugins_missing_from_the_lock() {
+        let resolved = [
+            locked("rip", "1.3.0", "aaaa"),
+            locked("mge", "2.0.0", "bbbb"),
+            locked("new", "1.0.0", "dddd"),
+        ];
+        let err = lockfile().verify(&resolved).unwrap_err().to_string();
+        assert!(err.contains("new is not in sm-pkg.lock"), "{}", err);
+    }
+
+    #[test]
+    fn verify_rejects_extra_locked_plugins() {
+        let resolved = [locked("rip", "1.3.0", "aaaa")];
+        let err = lockfile().verify(&resolved).unwrap_err().to_string();
+        assert!(
+            err.contains("mge is locked but no longer declared"),
+            "{}",
+            err
+        );
+        lockfile().verify_plugins(&resolved).unwrap();
+    }
+}
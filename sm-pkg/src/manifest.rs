@@ -0,0 +1,314 @@
+use crate::{BoxResult, MANIFEST_FILE, STATE_DIR, fsutil};
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+/// Record of every file sm-pkg has placed into the game directory, stored in .sm-pkg/manifest.yaml.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Manifest {
+    pub files: Vec<InstalledFile>,
+    /// Configuration files generated from the project definition.
+    #[serde(default)]
+    pub generated: Vec<GeneratedFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InstalledFile {
+    /// Path relative to the project root.
+    pub path: PathBuf,
+    /// Name of the plugin that owns the file.
+    pub plugin: String,
+    /// sha256 of the file contents as installed.
+    pub hash: String,
+    /// sha256 of the stock file when local edits were kept or merged into it on install.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stock_hash: Option<String>,
+}
+
+impl InstalledFile {
+    /// Hash of the file as shipped, before any local edits were carried over into it.
+    pub fn stock_hash(&self) -> &str {
+        self.stock_hash.as_deref().unwrap_or(&self.hash)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GeneratedFile {
+    /// Path relative to the project root.
+    pub path: PathBuf,
+    /// sha256 of the file contents as last written.
+    pub hash: String,
+}
+
+impl Manifest {
+    /// Open the project manifest, returning an empty one if nothing has been installed yet.
+    pub fn open(project_root: &Path) -> BoxResult<Self> {
+        let path = manifest_path(project_root);
+        if !path.exists() {
+            return Ok(Manifest::default());
+        }
+        let manifest: Manifest = serde_yaml::from_reader(File::open(path)?)?;
+        Ok(manifest)
+    }
+
+    pub fn save(&self, project_root: &Path) -> BoxResult {
+        fs::create_dir_all(project_root.join(STATE_DIR))?;
+        serde_yaml::to_writer(File::create(manifest_path(project_root))?, self)?;
+        Ok(())
+    }
+
+    pub fn find(&self, path: &Path) -> Option<&InstalledFile> {
+        self.files.iter().find(|f| f.path == path)
+    }
+
+    /// Copy a single file to `dest`, recording it as owned by `plugin`.
+    pub fn install_file(
+        &mut self,
+        project_root: &Path,
+        plugin: &str,
+        src: &Path,
+        dest: &Path,
+    ) -> BoxResult {
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(src, dest)?;
+        self.record(project_root, plugin, dest)
+    }
+
+    /// Record an already installed file as owned by `plugin`.
+    pub fn record(&mut self, project_root: &Path, plugin: &str, installed: &Path) -> BoxResult {
+        let path = installed.strip_prefix(project_root)?.to_path_buf();
+        let hash = fsutil::hash_file(installed)?;
+        self.files.retain(|f| f.path != path);
+        self.files.push(InstalledFile {
+            path,
+            plugin: plugin.to_string(),
+            hash,
+            stock_hash: None,
+        });
+        Ok(())
+    }
+
+    /// Update the hash of an installed file whose contents were replaced after it was recorded,
+    /// such as a stock config local edits were kept or merged into. The recorded hash is kept as
+    /// the stock one.
+    pub fn record_edited(&mut self, project_root: &Path, path: &Path) -> BoxResult {
+        let hash = fsutil::hash_file(project_root.join(path))?;
+        if let Some(file) = self.files.iter_mut().find(|f| f.path == path) {
+            file.stock_hash.get_or_insert_with(|| file.hash.clone());
+            file.hash = hash;
+        }
+        Ok(())
+    }
+
+    /// Record a generated configuration file that has just been written. A copy of it is kept
+    /// under the state directory so local edits can be diffed later.
+    pub fn record_generated(&mut self, project_root: &Path, written: &Path) -> BoxResult {
+        let path = written.strip_prefix(project_root)?.to_path_buf();
+        let hash = fsutil::hash_file(written)?;
+        let copy = generated_copy_path(project_root, &path);
+        if let Some(parent) = copy.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(written, copy)?;
+        self.generated.retain(|f| f.path != path);
+        self.generated.push(GeneratedFile { path, hash });
+        Ok(())
+    }
+
+    pub fn find_generated(&self, path: &Path) -> Option<&GeneratedFile> {
+        self.generated.iter().find(|f| f.path == path)
+    }
+
+    /// Generated files which have been edited by hand since sm-pkg last wrote them.
+    pub fn edited_generated(&self, project_root: &Path) -> BoxResult<Vec<&GeneratedFile>> {
+        let mut edited = Vec::new();
+        for file in &self.generated {
+            let full_path = project_root.join(&file.path);
+            if full_path.exists() && fsutil::hash_file(&full_path)? != file.hash {
+                edited.push(file);
+            }
+        }
+        Ok(edited)
+    }
+
+    /// Unified diff between the last generated version of a file and what is currently on disk.
+    pub fn diff_generated(&self, project_root: &Path, file: &GeneratedFile) -> BoxResult<String> {
+        let copy = generated_copy_path(project_root, &file.path);
+        if !copy.exists() {
+            return Err(format!(
+                "No copy of the last generated {} is available to diff against",
+                file.path.display()
+            )
+            .into());
+        }
+        let old = fs::read_to_string(copy)?;
+        let new = fs::read_to_string(project_root.join(&file.path))?;
+        let path = file.path.display();
+        let mut out = format!("--- a/{}\n+++ b/{}\n", path, path);
+        for hunk in TextDiff::from_lines(&old, &new).unified_diff().iter_hunks() {
+            out.push_str(&format!("{}\n", hunk.header()));
+            for change in hunk.iter_changes() {
+                let sign = match change.tag() {
+                    ChangeTag::Delete => '-',
+                    ChangeTag::Insert => '+',
+                    ChangeTag::Equal => ' ',
+                };
+                out.push(sign);
+                out.push_str(change.value());
+                if change.missing_newline() {
+                    out.push('\n');
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Files recorded in the `previous` manifest which are no longer part of this one. Generated
+    /// files that are no longer produced are included along with the copy kept of them.
+    pub fn stale(&self, previous: &Manifest) -> Vec<PathBuf> {
+        let owned = |path: &Path| self.find(path).is_some() || self.find_generated(path).is_some();
+        let mut stale: Vec<PathBuf> = previous
+            .files
+            .iter()
+            .filter(|old| !owned(&old.path))
+            .map(|old| old.path.clone())
+            .collect();
+        for old in &previous.generated {
+            if self.find_generated(&old.path).is_some() {
+                continue;
+            }
+            if !owned(&old.path) {
+                stale.push(old.path.clone());
+            }
+            stale.push(generated_copy(&old.path));
+        }
+        stale
+    }
+}
+
+fn manifest_path(project_root: &Path) -> PathBuf {
+    project_root.join(STATE_DIR).join(MANIFEST_FILE)
+}
+
+pub(crate) fn generated_copy_path(project_root: &Path, path: &Path) -> PathBuf {
+    project_root.join(generated_copy(path))
+}
+
+/// Copy of a generated file, relative to the project root.
+pub(crate) fn generated_copy(path: &Path) -> PathBuf {
+    Path::new(STATE_DIR).join("generated").join(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::write;
+
+    #[test]
+    fn record_generated_keeps_a_copy() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join("cfg/sourcemod/core.cfg");
+        write(&path, "first");
+        let mut manifest = Manifest::default();
+        manifest.record_generated(root.path(), &path).unwrap();
+        write(&path, "second");
+        manifest.record_generated(root.path(), &path).unwrap();
+
+        assert_eq!(manifest.generated.len(), 1);
+        let file = manifest
+            .find_generated(Path::new("cfg/sourcemod/core.cfg"))
+            .unwrap();
+        assert_eq!(file.hash, fsutil::hash_file(&path).unwrap());
+        let copy = generated_copy_path(root.path(), &file.path);
+        assert_eq!(fs::read_to_string(copy).unwrap(), "second");
+    }
+
+    fn installed(path: &str) -> InstalledFile {
+        InstalledFile {
+            path: PathBuf::from(path),
+            plugin: "rip".to_string(),
+            hash: String::new(),
+            stock_hash: None,
+        }
+    }
+
+    fn generated(path: &str) -> GeneratedFile {
+        GeneratedFile {
+            path: PathBuf::from(path),
+            hash: String::new(),
+        }
+    }
+
+    #[test]
+    fn stale_lists_files_no_longer_installed() {
+        let previous = Manifest {
+            files: vec![installed("plugins/rip.smx"), installed("plugins/old.smx")],
+            generated: vec![generated("cfg/sourcemod/rip.cfg")],
+        };
+        let current = Manifest {
+            files: vec![installed("plugins/rip.smx")],
+            generated: vec![generated("cfg/sourcemod/rip.cfg")],
+        };
+        assert_eq!(
+            current.stale(&previous),
+            vec![PathBuf::from("plugins/old.smx")]
+        );
+    }
+
+    #[test]
+    fn stale_prunes_generated_files_and_their_copies() {
+        let previous = Manifest {
+            files: vec![installed("cfg/sourcemod/sourcemod.cfg")],
+            generated: vec![
+                generated("cfg/sourcemod/core.cfg"),
+                generated("addons/sourcemod/configs/admins.cfg"),
+                generated("cfg/sourcemod/rip.cfg"),
+            ],
+        };
+        // admins.cfg was a template and is now the stock file, the stock sourcemod.cfg is now
+        // generated.
+        let current = Manifest {
+            files: vec![installed("addons/sourcemod/configs/admins.cfg")],
+            generated: vec![
+                generated("cfg/sourcemod/sourcemod.cfg"),
+                generated("cfg/sourcemod/rip.cfg"),
+            ],
+        };
+        assert_eq!(
+            current.stale(&previous),
+            vec![
+                PathBuf::from("cfg/sourcemod/core.cfg"),
+                PathBuf::from(".sm-pkg/generated/cfg/sourcemod/core.cfg"),
+                PathBuf::from(".sm-pkg/generated/addons/sourcemod/configs/admins.cfg"),
+            ]
+        );
+    }
+
+    #[test]
+    fn edits_to_generated_files_are_diffed() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join("cfg/server.cfg");
+        write(&path, "hostname \"a\"\nsv_cheats \"0\"\n");
+        let mut manifest = Manifest::default();
+        manifest.record_generated(root.path(), &path).unwrap();
+        assert!(manifest.edited_generated(root.path()).unwrap().is_empty());
+
+        write(&path, "hostname \"b\"\nsv_cheats \"0\"\n");
+        let edited = manifest.edited_generated(root.path()).unwrap();
+        assert_eq!(edited, vec![&manifest.generated[0]]);
+        assert_eq!(
+            manifest.diff_generated(root.path(), edited[0]).unwrap(),
+            "--- a/cfg/server.cfg\n+++ b/cfg/server.cfg\n@@ -1,2 +1,2 @@\n\
+             -hostname \"a\"\n+hostname \"b\"\n sv_cheats \"0\"\n"
+        );
+
+        // A removed file is not an edit.
+        fs::remove_file(&path).unwrap();
+        assert!(manifest.edited_generated(root.path()).unwrap().is_empty());
+    }
+}
@@ -4,10 +4,11 @@ use crate::{
     repo::PluginDefinitionProvider,
     sdk,
 };
+use semver::{Version, VersionReq};
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::{Debug, Display},
-    fs::create_dir_all,
+    fs::{self, create_dir_all},
     path::{Path, PathBuf},
     time::{SystemTime, UNIX_EPOCH},
 };
@@ -28,7 +29,87 @@ pub struct Definition {
     /// The full path to the plugin's directory
     pub path: Option<PathBuf>,
     pub configs: Option<Vec<SimpleConfig>>,
+    /// Games the plugin works with. Plugins without a list are assumed to work with any game.
     pub games: Option<Vec<Game>>,
+    /// Free form keywords used by search, eg: `mge`, `admin`.
+    pub tags: Option<Vec<String>>,
+    /// Prebuilt sourcemod extensions shipped with the plugin.
+    pub extensions: Option<Vec<Extension>>,
+    /// Gamedata files or directories, relative to the plugin's source root, eg: `gamedata/foo.txt`.
+    pub gamedata: Option<Vec<PathBuf>>,
+    /// Translation files or directories, relative to the plugin's source root, eg: `translations`.
+    pub translations: Option<Vec<PathBuf>>,
+}
+
+/// A prebuilt sourcemod extension, eg: REST in Pawn or SteamWorks.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Extension {
+    /// Extension name, used for the `<name>.autoload` file, eg: `rip`.
+    pub name: String,
+    /// 32-bit binary, installed into `addons/sourcemod/extensions`.
+    pub x86: Option<ExtensionBinary>,
+    /// 64-bit binary, installed into `addons/sourcemod/extensions/x64`.
+    pub x64: Option<ExtensionBinary>,
+    /// Load the extension on startup even when no plugin requires it. Defaults to true.
+    pub autoload: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ExtensionBinary {
+    /// Path to the binary relative to the plugin's source root, eg: `extensions/rip.ext.so`.
+    pub path: PathBuf,
+    /// Hex encoded sha256 of the binary.
+    pub sha256: String,
+}
+
+impl Definition {
+    /// Names of the plugins this one depends on, without any version requirements.
+    pub fn dependency_names(&self) -> impl Iterator<Item = &str> {
+        self.dependencies
+            .iter()
+            .flatten()
+            .map(|d| PluginSpec::name_of(d))
+    }
+
+    /// Whether the plugin can be used on `game`.
+    pub fn supports(&self, game: Game) -> bool {
+        match &self.games {
+            Some(games) => games.contains(&game),
+            None => true,
+        }
+    }
+
+    /// Games the plugin is limited to, eg: `CSTRIKE, DOD`.
+    pub fn supported_games(&self) -> String {
+        self.games
+            .iter()
+            .flatten()
+            .map(|g| format!("{:?}", g))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    /// Root of the plugin's source tree, the parent of its `scripting` directory.
+    pub fn source_root(&self) -> BoxResult<PathBuf> {
+        match self.path.as_ref().and_then(|p| p.parent()) {
+            Some(root) => Ok(root.to_path_buf()),
+            None => Err(format!("No source path known for plugin {}", self.name).into()),
+        }
+    }
+}
+
+impl Extension {
+    /// Declared binaries along with the directory under `addons/sourcemod` each belongs in.
+    pub fn binaries(&self) -> Vec<(&ExtensionBinary, PathBuf)> {
+        let mut binaries = Vec::new();
+        if let Some(x86) = &self.x86 {
+            binaries.push((x86, PathBuf::from("extensions")));
+        }
+        if let Some(x64) = &self.x64 {
+            binaries.push((x64, Path::new("extensions").join("x64")));
+        }
+        binaries
+    }
 }
 
 impl Display for Definition {
@@ -37,42 +118,382 @@ impl Display for Definition {
     }
 }
 
+/// A plugin reference as written in a plugin list, a name optionally followed by a version
+/// requirement, eg: `name`, `name@^1.2` or `name@2.0.1`.
+#[derive(Debug)]
+pub struct PluginSpec<'s> {
+    pub name: &'s str,
+    pub requirement: Option<VersionRequirement>,
+}
+
+impl<'s> PluginSpec<'s> {
+    pub fn parse(spec: &'s str) -> BoxResult<PluginSpec<'s>> {
+        let (name, requirement) = match spec.split_once('@') {
+            Some((name, requirement)) => (name, Some(VersionRequirement::parse(requirement)?)),
+            None => (spec, None),
+        };
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(format!("Invalid plugin name in {:?}", spec).into());
+        }
+        Ok(PluginSpec { name, requirement })
+    }
+
+    /// Name of the plugin `spec` refers to, without parsing its version requirement.
+    pub fn name_of(spec: &str) -> &str {
+        spec.split_once('@').map_or(spec, |(name, _)| name)
+    }
+
+    /// Fail if `definition` does not satisfy the version requirement.
+    pub fn check(&self, definition: &Definition) -> BoxResult {
+        match &self.requirement {
+            Some(requirement) if !requirement.matches(&definition.version) => Err(format!(
+                "{} {} does not satisfy the version requirement {}",
+                definition.name, definition.version, requirement
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Version requirement on a plugin. Semver is used when the requirement parses as one,
+/// otherwise the version must match the requirement exactly.
+#[derive(Debug)]
+pub enum VersionRequirement {
+    Semver(VersionReq),
+    Exact(String),
+}
+
+impl VersionRequirement {
+    /// Parse a requirement. A bare version, eg: `1.2.3`, must match exactly rather than being
+    /// read as `^1.2.3`. Anything that isn't semver but is still a single version, eg: `=r42`,
+    /// is matched as a string. Malformed semver requirements are rejected.
+    pub fn parse(requirement: &str) -> BoxResult<VersionRequirement> {
+        let operators = ['<', '>', '=', '^', '~', '*', ','];
+        if !requirement.contains(operators)
+            && let Some(version) = parse_version(requirement)
+        {
+            return Ok(VersionRequirement::Semver(VersionReq::parse(&format!(
+                "={}",
+                version
+            ))?));
+        }
+        if let Ok(req) = VersionReq::parse(requirement) {
+            return Ok(VersionRequirement::Semver(req));
+        }
+        let exact = requirement.strip_prefix('=').unwrap_or(requirement);
+        if exact.is_empty() || exact.contains(char::is_whitespace) || exact.contains(operators) {
+            return Err(format!("Invalid version requirement {:?}", requirement).into());
+        }
+        Ok(VersionRequirement::Exact(exact.to_string()))
+    }
+
+    /// Loosest requirement still accepting `version` without allowing breaking changes, eg:
+    /// `^1.3` for 1.3 or `=r42` for versions that are not semver.
+    pub fn compatible_with(version: &str) -> String {
+        match parse_version(version) {
+            Some(_) => format!("^{}", version.trim().trim_start_matches('v')),
+            None => format!("={}", version),
+        }
+    }
+
+    pub fn matches(&self, version: &str) -> bool {
+        match self {
+            VersionRequirement::Semver(req) => match parse_version(version) {
+                Some(version) => req.matches(&version),
+                None => req.to_string().trim_start_matches('=') == version,
+            },
+            VersionRequirement::Exact(exact) => exact == version,
+        }
+    }
+}
+
+impl Display for VersionRequirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VersionRequirement::Semver(req) => write!(f, "{}", req),
+            VersionRequirement::Exact(exact) => write!(f, "{}", exact),
+        }
+    }
+}
+
+/// Parse a plugin version as semver, accepting the short `1.2` and `v1.2.3` forms plugins
+/// commonly use.
+fn parse_version(version: &str) -> Option<Version> {
+    let version = version.trim().trim_start_matches('v');
+    if let Ok(version) = Version::parse(version) {
+        return Some(version);
+    }
+    let parts: Vec<&str> = version.split('.').collect();
+    if parts.len() < 3 && parts.iter().all(|p| p.parse::<u64>().is_ok()) {
+        let mut padded = parts.clone();
+        padded.resize(3, "0");
+        return Version::parse(&padded.join(".")).ok();
+    }
+    None
+}
+
+/// Fail if any of `plugins` does not support `game`.
+pub fn check_games(plugins: &[Definition], game: Game) -> BoxResult {
+    let incompatible: Vec<String> = plugins
+        .iter()
+        .filter(|p| !p.supports(game))
+        .map(|p| format!("{} (supports {})", p.name, p.supported_games()))
+        .collect();
+    match incompatible.is_empty() {
+        true => Ok(()),
+        false => Err(format!(
+            "❗ Plugins not compatible with {:?}: {}",
+            game,
+            incompatible.join(", ")
+        )
+        .into()),
+    }
+}
+
+/// Files of a built plugin that are needed at runtime, as (source, destination) pairs. Sources
+/// are inside `build_dir` as returned by [`build`], destinations are relative to
+/// `addons/sourcemod`. Compiled `.smx` plugins, the `configs` directory and any declared gamedata
+/// and translations are included, sources and includes never are.
+pub fn runtime_files(plugin: &Definition, build_dir: &Path) -> BoxResult<Vec<(PathBuf, PathBuf)>> {
+    let mut files = Vec::new();
+    for dir in ["plugins", "configs"] {
+        if build_dir.join(dir).is_dir() {
+            for rel in fsutil::list_files(build_dir.join(dir))? {
+                if dir == "plugins" && rel.extension().is_none_or(|ext| ext != "smx") {
+                    continue;
+                }
+                files.push((build_dir.join(dir).join(&rel), Path::new(dir).join(rel)));
+            }
+        }
+    }
+    let declared = [
+        ("gamedata", &plugin.gamedata),
+        ("translations", &plugin.translations),
+    ];
+    for (dir, paths) in declared {
+        for path in paths.iter().flatten() {
+            let src = build_dir.join(path);
+            // Keep any structure below the conventional directory, translations are grouped by
+            // language. Anything declared elsewhere lands at the top of it.
+            let dest = match path.strip_prefix(dir) {
+                Ok(rest) => Path::new(dir).join(rest),
+                Err(_) if src.is_dir() => PathBuf::from(dir),
+                Err(_) => Path::new(dir).join(path.file_name().unwrap_or_default()),
+            };
+            if src.is_dir() {
+                for rel in fsutil::list_files(&src)? {
+                    files.push((src.join(&rel), dest.join(rel)));
+                }
+            } else if src.is_file() {
+                files.push((src, dest));
+            } else {
+                return Err(format!(
+                    "❗ {} file of {} not found: {}",
+                    dir,
+                    plugin.name,
+                    path.display()
+                )
+                .into());
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Ensure every extension binary shipped by `plugins` exists and matches its checksum.
+pub fn verify_extensions(plugins: &[Definition]) -> BoxResult {
+    for plugin in plugins {
+        let src_root = match &plugin.extensions {
+            Some(extensions) if !extensions.is_empty() => plugin.source_root()?,
+            _ => continue,
+        };
+        for extension in plugin.extensions.iter().flatten() {
+            if extension.binaries().is_empty() {
+                return Err(format!(
+                    "❗ Extension {} of {} has no binaries",
+                    extension.name, plugin.name
+                )
+                .into());
+            }
+            for (binary, _) in extension.binaries() {
+                let path = src_root.join(&binary.path);
+                if !path.exists() {
+                    return Err(format!(
+                        "❗ Extension binary of {} not found: {}",
+                        plugin.name,
+                        path.display()
+                    )
+                    .into());
+                }
+                let hash = fsutil::hash_file(&path)?;
+                if !hash.eq_ignore_ascii_case(&binary.sha256) {
+                    return Err(format!(
+                        "❗ Checksum mismatch for {}: expected {}, got {}",
+                        path.display(),
+                        binary.sha256,
+                        hash
+                    )
+                    .into());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Copy the extensions of a plugin into `sm_root`, creating `.autoload` files as needed.
+/// Returns every file written. Binaries should already have been checked with
+/// [`verify_extensions`].
+pub fn install_extensions(plugin: &Definition, sm_root: &Path) -> BoxResult<Vec<PathBuf>> {
+    let mut installed = Vec::new();
+    let extensions = match &plugin.extensions {
+        Some(extensions) if !extensions.is_empty() => extensions,
+        _ => return Ok(installed),
+    };
+    let src_root = plugin.source_root()?;
+    for extension in extensions {
+        for (binary, dir) in extension.binaries() {
+            let file_name = match binary.path.file_name() {
+                Some(file_name) => file_name,
+                None => {
+                    return Err(format!("Invalid extension path: {}", binary.path.display()).into());
+                }
+            };
+            let dest_dir = sm_root.join(dir);
+            create_dir_all(&dest_dir)?;
+            let dest = dest_dir.join(file_name);
+            fs::copy(src_root.join(&binary.path), &dest)?;
+            installed.push(dest);
+        }
+        if extension.autoload.unwrap_or(true) {
+            let autoload = sm_root
+                .join("extensions")
+                .join(format!("{}.autoload", extension.name));
+            fs::write(&autoload, "")?;
+            installed.push(autoload);
+        }
+    }
+    Ok(installed)
+}
+
+/// Resolve the full transitive closure of the requested plugins. The result is in build order,
+/// every plugin comes after all of its dependencies. Plugins may carry version requirements,
+/// see [`PluginSpec`].
+pub fn resolve(
+    repo: &dyn PluginDefinitionProvider,
+    plugins: &[String],
+) -> BoxResult<Vec<Definition>> {
+    let mut resolved = Vec::new();
+    let mut chain = Vec::new();
+    for plugin in plugins {
+        resolve_into(repo, plugin, &mut chain, &mut resolved)?;
+    }
+    Ok(resolved)
+}
+
+fn resolve_into(
+    repo: &dyn PluginDefinitionProvider,
+    spec: &str,
+    chain: &mut Vec<String>,
+    resolved: &mut Vec<Definition>,
+) -> BoxResult {
+    let required_by = |chain: &[String]| match chain.is_empty() {
+        true => String::new(),
+        false => format!(" (required by {})", chain.join(" -> ")),
+    };
+    let spec = PluginSpec::parse(spec).map_err(|e| format!("{}{}", e, required_by(chain)))?;
+    let plugin = spec.name;
+    if let Some(definition) = resolved.iter().find(|d| d.name == plugin) {
+        return spec
+            .check(definition)
+            .map_err(|e| format!("{}{}", e, required_by(chain)).into());
+    }
+    if chain.iter().any(|p| p == plugin) {
+        return Err(format!(
+            "Dependency cycle detected: {} -> {}",
+            chain.join(" -> "),
+            plugin
+        )
+        .into());
+    }
+    let definition = match repo.find_plugin_definition(plugin) {
+        Ok(definition) => definition,
+        Err(_) if !chain.is_empty() => {
+            return Err(format!("Plugin not found: {}{}", plugin, required_by(chain)).into());
+        }
+        Err(e) => return Err(e),
+    };
+    spec.check(&definition)
+        .map_err(|e| format!("{}{}", e, required_by(chain)))?;
+
+    chain.push(plugin.to_string());
+    for dep in definition.dependencies.iter().flatten() {
+        resolve_into(repo, dep, chain, resolved)?;
+    }
+    chain.pop();
+    resolved.push(definition);
+
+    Ok(())
+}
+
+/// All direct and indirect dependencies of a plugin, looked up from an already resolved set.
+fn dependency_closure<'d>(plugin: &Definition, resolved: &'d [Definition]) -> Vec<&'d Definition> {
+    let mut closure: Vec<&Definition> = Vec::new();
+    let mut pending: Vec<&str> = plugin.dependency_names().collect();
+    while let Some(name) = pending.pop() {
+        if closure.iter().any(|d| d.name == name) {
+            continue;
+        }
+        if let Some(dep) = resolved.iter().find(|d| d.name == name) {
+            pending.extend(dep.dependency_names());
+            closure.push(dep);
+        }
+    }
+    closure
+}
+
+/// Build a set of plugins as returned by [`resolve`], returning the build directory of each one in the same order.
 pub fn build(
-    app_root: &Path,
     sdk_env: &sdk::Environment,
     build_root: &Path,
-    repo: &dyn PluginDefinitionProvider,
-    plugins: &[String],
+    plugins: &[Definition],
 ) -> BoxResult<Vec<PathBuf>> {
     let mut outputs = Vec::new();
-    for plugin in repo.find_plugin_definitions(plugins)? {
-        let src_tree = app_root.join("repo").join(&plugin.name).join("src");
+    for plugin in plugins {
+        let src_tree = plugin.source_root()?;
         let build_dir = build_root.join(&plugin.name);
         create_dir_all(build_dir.join("include"))?;
         fsutil::copy_dir_all(src_tree, &build_dir)?;
 
-        if let Some(deps) = &plugin.dependencies {
-            let include_dir = build_dir.join("include");
-            create_dir_all(&include_dir)?;
-            for dep in deps {
-                let inc_tree = app_root
-                    .join("repo")
-                    .join(dep)
-                    .join("src/scripting/include");
-                if !inc_tree.exists() {
+        let include_dir = build_dir.join("include");
+        for dep in plugin.dependency_names() {
+            if !plugins.iter().any(|p| p.name == dep) {
+                return Err(format!(
+                    "Dependency {} of {} has not been resolved",
+                    dep, plugin.name
+                )
+                .into());
+            }
+        }
+        for dep in dependency_closure(plugin, plugins) {
+            let inc_tree = dep.source_root()?.join("scripting/include");
+            if !inc_tree.exists() {
+                if plugin.dependency_names().any(|d| d == dep.name) {
                     return Err(
                         format!("Dependency include directory not found: {:?}", inc_tree).into(),
                     );
                 }
-                debug!("Adding {} includes", dep);
-                fsutil::copy_dir_all(inc_tree, &include_dir)?;
+                continue;
             }
+            debug!("Adding {} includes", dep.name);
+            fsutil::copy_dir_all(inc_tree, &include_dir)?;
         }
         let mut args = sdk_env.args();
         args.active_dir = Some(build_dir.join("scripting"));
         // The path must be a full absolute path
         args.include(build_dir.clone().join("include").canonicalize()?);
-        sdk_env.compile(&mut args, &plugin)?;
+        sdk_env.compile(&mut args, plugin)?;
         outputs.push(build_dir);
     }
 
@@ -89,3 +510,233 @@ pub fn create_build_root(app_root: &Path) -> BoxResult<PathBuf> {
     create_dir_all(&build_root)?;
     Ok(build_root)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{self, TestRepo, write};
+
+    fn names(resolved: &[Definition]) -> Vec<&str> {
+        resolved.iter().map(|d| d.name.as_str()).collect()
+    }
+
+    #[test]
+    fn resolve_orders_diamond_dependencies() {
+        let repo = TestRepo::new(&[
+            ("app", "1.0.0", &["left", "right"]),
+            ("left", "1.0.0", &["base"]),
+            ("right", "1.0.0", &["base"]),
+            ("base", "1.0.0", &[]),
+        ]);
+        let resolved = resolve(&repo, &["app".to_string()]).unwrap();
+        assert_eq!(names(&resolved), vec!["base", "left", "right", "app"]);
+    }
+
+    #[test]
+    fn resolve_reports_cycle_chain() {
+        let repo = TestRepo::new(&[
+            ("a", "1.0.0", &["b"]),
+            ("b", "1.0.0", &["c"]),
+            ("c", "1.0.0", &["a"]),
+        ]);
+        let err = resolve(&repo, &["a".to_string()]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Dependency cycle detected: a -> b -> c -> a"
+        );
+    }
+
+    #[test]
+    fn resolve_reports_missing_transitive_dependency() {
+        let repo = TestRepo::new(&[("app", "1.0.0", &["lib"]), ("lib", "1.0.0", &["gone"])]);
+        let err = resolve(&repo, &["app".to_string()]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Plugin not found: gone (required by app -> lib)"
+        );
+    }
+
+    #[test]
+    fn resolve_checks_requirements_of_shared_dependencies() {
+        let repo = TestRepo::new(&[
+            ("app", "1.0.0", &["base@>=2"]),
+            ("tool", "1.0.0", &["base@^1"]),
+            ("base", "1.4.0", &[]),
+        ]);
+        let err = resolve(&repo, &["tool".to_string(), "app".to_string()]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "base 1.4.0 does not satisfy the version requirement >=2 (required by app)"
+        );
+    }
+
+    #[test]
+    fn parse_bare_version_is_exact() {
+        let spec = PluginSpec::parse("rip@1.2.3").unwrap();
+        assert_eq!(spec.name, "rip");
+        let requirement = spec.requirement.unwrap();
+        assert!(requirement.matches("1.2.3"));
+        assert!(requirement.matches("v1.2.3"));
+        assert!(!requirement.matches("1.2.4"));
+        assert!(!requirement.matches("1.3.0"));
+    }
+
+    #[test]
+    fn parse_semver_range() {
+        let spec = PluginSpec::parse("rip@>=2, <3").unwrap();
+        assert_eq!(spec.name, "rip");
+        let requirement = spec.requirement.unwrap();
+        assert!(matches!(requirement, VersionRequirement::Semver(_)));
+        assert!(requirement.matches("2.0.0"));
+        assert!(requirement.matches("2.9"));
+        assert!(!requirement.matches("1.9.9"));
+        assert!(!requirement.matches("3.0.0"));
+    }
+
+    #[test]
+    fn parse_bare_name() {
+        let spec = PluginSpec::parse("rip").unwrap();
+        assert_eq!(spec.name, "rip");
+        assert!(spec.requirement.is_none());
+    }
+
+    #[test]
+    fn parse_non_semver_versions_exactly() {
+        let requirement = PluginSpec::parse("rip@=r42").unwrap().requirement.unwrap();
+        assert!(matches!(requirement, VersionRequirement::Exact(_)));
+        assert!(requirement.matches("r42"));
+        assert!(!requirement.matches("r43"));
+    }
+
+    #[test]
+    fn parse_rejects_malformed_specs() {
+        for spec in [
+            "rip@",
+            "@1.0.0",
+            "rip@>=",
+            "rip@>=2, <",
+            "rip@1.0 beta",
+            "my plugin",
+        ] {
+            assert!(PluginSpec::parse(spec).is_err(), "{} was accepted", spec);
+        }
+    }
+
+    /// A plugin shipping a 64-bit `rip` extension, with its sources under `root`.
+    fn extension_plugin(root: &Path, binary: &str, autoload: Option<bool>) -> Definition {
+        write(&root.join("extensions/rip.ext.so"), binary);
+        let mut plugin = testutil::definition("rip", "1.0.0");
+        plugin.path = Some(root.join("scripting"));
+        plugin.extensions = Some(vec![Extension {
+            name: "rip".to_string(),
+            x86: None,
+            x64: Some(ExtensionBinary {
+                path: PathBuf::from("extensions/rip.ext.so"),
+                sha256: fsutil::hash_file(root.join("extensions/rip.ext.so")).unwrap(),
+            }),
+            autoload,
+        }]);
+        plugin
+    }
+
+    #[test]
+    fn extensions_are_installed_with_an_autoload_file() {
+        let src = tempfile::tempdir().unwrap();
+        let sm_root = tempfile::tempdir().unwrap();
+        let plugin = extension_plugin(src.path(), "elf", None);
+        verify_extensions(std::slice::from_ref(&plugin)).unwrap();
+
+        let installed = install_extensions(&plugin, sm_root.path()).unwrap();
+        assert_eq!(
+            installed,
+            vec![
+                sm_root.path().join("extensions/x64/rip.ext.so"),
+                sm_root.path().join("extensions/rip.autoload"),
+            ]
+        );
+        assert_eq!(fs::read_to_string(&installed[0]).unwrap(), "elf");
+
+        let sm_root = tempfile::tempdir().unwrap();
+        let plugin = extension_plugin(src.path(), "elf", Some(false));
+        let installed = install_extensions(&plugin, sm_root.path()).unwrap();
+        assert_eq!(
+            installed,
+            vec![sm_root.path().join("extensions/x64/rip.ext.so")]
+        );
+    }
+
+    #[test]
+    fn missing_or_modified_extension_binaries_are_rejected() {
+        let src = tempfile::tempdir().unwrap();
+        let plugin = extension_plugin(src.path(), "elf", None);
+        write(&src.path().join("extensions/rip.ext.so"), "tampered");
+        let err = verify_extensions(std::slice::from_ref(&plugin)).unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch"), "{}", err);
+
+        fs::remove_file(src.path().join("extensions/rip.ext.so")).unwrap();
+        let err = verify_extensions(&[plugin]).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Extension binary of rip not found"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn runtime_files_exclude_sources_and_undeclared_files() {
+        let build = tempfile::tempdir().unwrap();
+        for file in [
+            "scripting/rip.sp",
+            "scripting/include/rip.inc",
+            "include/dep.inc",
+            "plugins/rip.smx",
+            "plugins/disabled/old.smx",
+            "plugins/README.md",
+            "configs/rip.cfg",
+            "gamedata/rip.txt",
+            "gamedata/undeclared.txt",
+            "data/rip.games.txt",
+            "translations/rip.phrases.txt",
+            "translations/de/rip.phrases.txt",
+        ] {
+            write(&build.path().join(file), file);
+        }
+        let mut plugin = testutil::definition("rip", "1.0.0");
+        plugin.gamedata = Some(vec![
+            PathBuf::from("gamedata/rip.txt"),
+            PathBuf::from("data/rip.games.txt"),
+        ]);
+        plugin.translations = Some(vec![PathBuf::from("translations")]);
+
+        let mut files = runtime_files(&plugin, build.path()).unwrap();
+        files.sort();
+        let expected: Vec<(PathBuf, PathBuf)> = [
+            ("configs/rip.cfg", "configs/rip.cfg"),
+            ("data/rip.games.txt", "gamedata/rip.games.txt"),
+            ("gamedata/rip.txt", "gamedata/rip.txt"),
+            ("plugins/disabled/old.smx", "plugins/disabled/old.smx"),
+            ("plugins/rip.smx", "plugins/rip.smx"),
+            (
+                "translations/de/rip.phrases.txt",
+                "translations/de/rip.phrases.txt",
+            ),
+            (
+                "translations/rip.phrases.txt",
+                "translations/rip.phrases.txt",
+            ),
+        ]
+        .iter()
+        .map(|(src, dest)| (build.path().join(src), PathBuf::from(dest)))
+        .collect();
+        assert_eq!(files, expected);
+
+        plugin.gamedata = Some(vec![PathBuf::from("gamedata/missing.txt")]);
+        let err = runtime_files(&plugin, build.path()).unwrap_err();
+        assert!(
+            err.to_string().contains("gamedata file of rip not found"),
+            "{}",
+            err
+        );
+    }
+}
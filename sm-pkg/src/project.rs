@@ -1,7 +1,13 @@
-use crate::repo::PluginDefinitionProvider;
-use crate::{BoxResult, PROJECT_FILE, VERSION, plugins, sdk, templates};
+use crate::games::{self, GameInfo};
+use crate::layers::{self, Extends};
+use crate::manifest::Manifest;
+use crate::plugins::{PluginSpec, VersionRequirement};
+use crate::prompt::Prompter;
+use crate::repo::{PluginDefinitionProvider, overlay::OverlaySource};
+use crate::stage::Stage;
+use crate::{BoxResult, PROJECT_FILE, SCHEMA_URL, VERSION, plugins, sdk, templates};
 use askama::Template;
-use inquire::{InquireError, Select};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::io::Write as _;
 use std::os::unix::fs::PermissionsExt;
@@ -11,7 +17,10 @@ use std::{fmt, fs};
 
 // https://wiki.alliedmods.net/Required_Versions_%28SourceMod%29
 // https://github.com/alliedmodders/sourcemod/tree/master/gamedata/sdktools.games
-#[derive(clap::ValueEnum, Clone, Debug, Serialize, Default, Deserialize)]
+#[derive(
+    clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Default, Deserialize, JsonSchema,
+)]
+#[schemars(transform = game_aliases)]
 pub enum Game {
     AG2,
     ALIENSWARM,
@@ -62,161 +71,187 @@ pub enum Game {
     ZPANIC,
 }
 
+fn game_aliases(schema: &mut schemars::Schema) {
+    crate::schema::extend_enum(schema, &["tf", "Tf"]);
+}
+
 impl Game {
+    /// Registry entry describing this game.
+    pub fn info(&self) -> &'static GameInfo {
+        games::lookup(*self)
+    }
+
     pub fn mod_folder(&self) -> &Path {
-        match self {
-            Game::AG2 => todo!(),
-            Game::ALIENSWARM => todo!(),
-            Game::AOC => todo!(),
-            Game::BG2 => todo!(),
-            Game::BMS => todo!(),
-            Game::CSPROMOD => todo!(),
-            Game::CSTRIKE => todo!(),
-            Game::DINODDAY => todo!(),
-            Game::DOD => todo!(),
-            Game::DOI => todo!(),
-            Game::DYSTOPIA => todo!(),
-            Game::EMPIRES => todo!(),
-            Game::ESMOD => todo!(),
-            Game::FAS => todo!(),
-            Game::FF => todo!(),
-            Game::FOF => todo!(),
-            Game::GESOURCE => todo!(),
-            Game::GMOD9 => todo!(),
-            Game::HIDDEN => todo!(),
-            Game::HL1MP => todo!(),
-            Game::HL2CTF => todo!(),
-            Game::HL2MP => todo!(),
-            Game::INSURGENCY => todo!(),
-            Game::IOS => todo!(),
-            Game::KZ => todo!(),
-            Game::LEFT4DEAD2 => todo!(),
-            Game::MODULARCOMBAT => todo!(),
-            Game::NEOTOKYO => todo!(),
-            Game::NMRIH => todo!(),
-            Game::NUCLEARDAWN => todo!(),
-            Game::OBSIDIAN => todo!(),
-            Game::OPENFORTRESS => todo!(),
-            Game::PF2 => todo!(),
-            Game::PVKILL => todo!(),
-            Game::REACTIVEDROP => todo!(),
-            Game::MKBETA => todo!(),
-            Game::SHIP => todo!(),
-            Game::SOURCEFORTS => todo!(),
-            Game::SYNERGY => todo!(),
-            Game::TF => Path::new("tf"),
-            Game::TF2CLASSIC => todo!(),
-            Game::TF2CLASSIFIED => todo!(),
-            Game::TREASON => todo!(),
-            Game::ZM => todo!(),
-            Game::ZPANIC => todo!(),
-        }
+        Path::new(self.info().mod_folder)
+    }
+
+    /// All known games, in registry order.
+    pub fn all() -> Vec<Game> {
+        games::GAMES.iter().map(|info| info.game).collect()
     }
 }
 
 impl Display for Game {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Game::AG2 => todo!(),
-            Game::ALIENSWARM => todo!(),
-            Game::AOC => todo!(),
-            Game::BG2 => todo!(),
-            Game::BMS => todo!(),
-            Game::CSPROMOD => todo!(),
-            Game::CSTRIKE => todo!(),
-            Game::DINODDAY => todo!(),
-            Game::DOD => todo!(),
-            Game::DOI => todo!(),
-            Game::DYSTOPIA => todo!(),
-            Game::EMPIRES => todo!(),
-            Game::ESMOD => todo!(),
-            Game::FAS => todo!(),
-            Game::FF => todo!(),
-            Game::FOF => todo!(),
-            Game::GESOURCE => todo!(),
-            Game::GMOD9 => todo!(),
-            Game::HIDDEN => todo!(),
-            Game::HL1MP => todo!(),
-            Game::HL2CTF => todo!(),
-            Game::HL2MP => todo!(),
-            Game::INSURGENCY => todo!(),
-            Game::IOS => todo!(),
-            Game::KZ => todo!(),
-            Game::LEFT4DEAD2 => todo!(),
-            Game::MODULARCOMBAT => todo!(),
-            Game::NEOTOKYO => todo!(),
-            Game::NMRIH => todo!(),
-            Game::NUCLEARDAWN => todo!(),
-            Game::OBSIDIAN => todo!(),
-            Game::OPENFORTRESS => todo!(),
-            Game::PF2 => todo!(),
-            Game::PVKILL => todo!(),
-            Game::REACTIVEDROP => todo!(),
-            Game::MKBETA => todo!(),
-            Game::SHIP => todo!(),
-            Game::SOURCEFORTS => todo!(),
-            Game::SYNERGY => todo!(),
-            Game::TF => write!(f, "Team Fortress 2"),
-            Game::TF2CLASSIC => todo!(),
-            Game::TF2CLASSIFIED => todo!(),
-            Game::TREASON => todo!(),
-            Game::ZM => todo!(),
-            Game::ZPANIC => todo!(),
-        }
+        write!(f, "{}", self.info().name)
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[schemars(title = "sm-pkg project definition")]
+#[serde(deny_unknown_fields)]
 pub struct Package {
+    /// Project files this one is layered on top of, relative to this file. They are deep merged
+    /// in order, lists are appended and anything else is overridden by later files.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extends: Option<Extends>,
+    /// Game server type.
     pub game: Game,
+    /// Metamod and sourcemod branch to install.
     pub branch: sdk::Branch,
+    /// Sourcemod build to install instead of the branch's latest. Either an exact build, eg:
+    /// `1.12.0-git7210`, or a range, eg: `>=1.12.0-git7100, <1.12.0-git7300`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sourcemod_version: Option<String>,
+    /// Metamod build to install instead of the branch's latest. Either an exact build, eg:
+    /// `1.12.0-git1219`, or a range, eg: `>=1.12.0-git1200`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metamod_version: Option<String>,
+    /// Plugins the project uses.
     pub plugins: Vec<String>,
+    /// Plugins that were pulled in automatically as dependencies of `plugins`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
+    /// Overrides the game's default mod folder, relative to the project root.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mod_folder: Option<PathBuf>,
+    /// Generate a startup script for the game server.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub create_startup_script: Option<bool>,
+    /// Options used when generating the startup script.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub startup_opts: Option<templates::StartSh>,
+    /// Project description.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    /// Sourcemod configuration files generated from templates.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub templates: Option<TemplateSet>,
+    /// Arbitrary key value config files to generate.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub raw_configs: Option<Vec<SimpleConfig>>,
+    /// Plugin config files to generate, overriding the defaults shipped with a plugin.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub plugin_configs: Option<Vec<SimpleConfig>>,
+    /// Extra plugin sources stacked over the default repository, see `OverlaySource`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overlays: Option<Vec<OverlaySource>>,
+}
+
+impl Package {
+    /// Every plugin declared by the project, explicit plugins first followed by dependencies.
+    /// Entries may carry a version requirement, see [`plugins::PluginSpec`].
+    pub fn declared_plugins(&self) -> Vec<String> {
+        self.plugins
+            .iter()
+            .chain(self.dependencies.iter())
+            .cloned()
+            .collect()
+    }
+
+    /// Names of every declared plugin, without version requirements.
+    pub fn declared_names(&self) -> Vec<&str> {
+        self.plugins
+            .iter()
+            .chain(self.dependencies.iter())
+            .map(|p| PluginSpec::name_of(p))
+            .collect()
+    }
+
+    /// Build requirement declared for `runtime`, if any.
+    pub fn build_requirement(
+        &self,
+        runtime: &sdk::Runtime,
+    ) -> BoxResult<Option<sdk::BuildRequirement>> {
+        let (key, requirement) = match runtime {
+            sdk::Runtime::Sourcemod => ("sourcemod_version", &self.sourcemod_version),
+            sdk::Runtime::Metamod => ("metamod_version", &self.metamod_version),
+        };
+        match requirement {
+            None => Ok(None),
+            Some(requirement) => match sdk::BuildRequirement::parse(requirement) {
+                Ok(requirement) => Ok(Some(requirement)),
+                Err(e) => Err(format!("❗ Invalid {}: {}", key, e).into()),
+            },
+        }
+    }
+
+    /// The mod folder configs and addons are installed into, relative to the project root.
+    pub fn mod_folder(&self) -> &Path {
+        match &self.mod_folder {
+            Some(path) => path,
+            None => self.game.mod_folder(),
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct SimpleConfig {
+    /// Path to the config file, relative to the project root.
     pub path: PathBuf,
+    /// Key value config pairs.
     pub options: HashMap<String, String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct TemplateSet {
+    /// cfg/sourcemod/sourcemod.cfg
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sourcemod_cfg: Option<templates::SourcemodCfg>,
+    /// addons/sourcemod/configs/maplists.cfg
     #[serde(skip_serializing_if = "Option::is_none")]
     pub maplists_cfg: Option<templates::MaplistsCfg>,
+    /// addons/sourcemod/configs/databases.cfg
     #[serde(skip_serializing_if = "Option::is_none")]
     pub databases_cfg: Option<templates::DatabasesCfg>,
+    /// addons/sourcemod/configs/core.cfg
     #[serde(skip_serializing_if = "Option::is_none")]
     pub core_cfg: Option<templates::CoreCfg>,
+    /// addons/sourcemod/configs/admins_simple.ini
     #[serde(skip_serializing_if = "Option::is_none")]
     pub admins_simple_ini: Option<templates::AdminsSimpleIni>,
+    /// addons/sourcemod/configs/admins.cfg
     #[serde(skip_serializing_if = "Option::is_none")]
     pub admins_cfg: Option<templates::AdminsCfg>,
+    /// addons/sourcemod/configs/admin_groups.cfg
     #[serde(skip_serializing_if = "Option::is_none")]
     pub admin_groups_cfg: Option<templates::AdminGroupsCfg>,
+    /// addons/sourcemod/configs/admin_overrides.cfg
     #[serde(skip_serializing_if = "Option::is_none")]
     pub admin_overrides_cfg: Option<templates::AdminOverridesCfg>,
 }
 
+/// Answers for `init` that were given up front instead of being prompted for.
+#[derive(Debug, Default)]
+pub struct InitOptions {
+    pub game: Option<Game>,
+    pub branch: Option<sdk::Branch>,
+    pub plugins: Vec<String>,
+    /// Use the default for anything that was not given instead of prompting.
+    pub yes: bool,
+}
+
 /// Manager is responsible for loading and managing a project using its package configuration file, sm-pkg.yaml.
 pub struct Project<'a> {
     /// Root directory of the project.
     project_root: &'a Path,
     pub package: Option<Package>,
     repo: &'a dyn PluginDefinitionProvider<'a>,
+    /// For projects using `extends`, the merged base layers and the project file as it was
+    /// loaded. Used to write back only what belongs to this project.
+    layered: Option<(serde_yaml::Value, serde_yaml::Value)>,
 }
 
 impl<'a> Project<'a> {
@@ -229,13 +264,20 @@ impl<'a> Project<'a> {
             project_root,
             repo,
             package: None,
+            layered: None,
         })
     }
 
-    pub fn open_or_new(&mut self) -> BoxResult {
-        match self.project_file_path().exists() {
+    pub fn open_or_new(&mut self, options: &InitOptions, prompter: &dyn Prompter) -> BoxResult {
+        let exists = self.project_file_path().exists();
+        if exists
+            && (options.game.is_some() || options.branch.is_some() || !options.plugins.is_empty())
+        {
+            warn!("{} already exists, ignoring init options", PROJECT_FILE);
+        }
+        match exists {
             true => Some(self.existing_project()?),
-            false => Some(self.create_package_config()?),
+            false => Some(self.create_package_config(options, prompter)?),
         };
         debug!("Loaded package config {:?}", self.project_file_path());
         Ok(())
@@ -265,13 +307,15 @@ impl<'a> Project<'a> {
             Some(ref config) => config,
             None => return Err("❗ No config?".into()),
         };
+        let mut document = serde_yaml::to_value(config)?;
+        if let Some((base, local)) = &self.layered {
+            document = layers::strip_base(&document, base, Some(local))?
+                .unwrap_or(serde_yaml::Value::Mapping(Default::default()));
+        }
         let mut file = File::create(self.project_file_path())?;
-        writeln!(
-            file,
-            "# yaml-language-server: $schema=https://raw.githubusercontent.com/sm-pkg/sm-pkg/refs/heads/master/schema/sm-pkg.json"
-        )?;
+        writeln!(file, "# yaml-language-server: $schema={}", SCHEMA_URL)?;
         writeln!(file, "---")?;
-        serde_yaml::to_writer(file, &config)?;
+        serde_yaml::to_writer(file, &document)?;
 
         Ok(())
     }
@@ -281,108 +325,366 @@ impl<'a> Project<'a> {
             None => false,
             Some(ref config) => config
                 .plugins
-                .contains(&plugin_name.to_string().to_lowercase()),
+                .iter()
+                .any(|p| PluginSpec::name_of(p) == plugin_name.to_lowercase()),
+        }
+    }
+
+    /// Declared plugins which depend on `plugin_name`, directly or indirectly.
+    pub fn reverse_dependencies(&self, plugin_name: &str) -> Vec<String> {
+        let pkg = match &self.package {
+            Some(pkg) => pkg,
+            None => return Vec::new(),
+        };
+        let mut dependents = Vec::new();
+        for declared in pkg.declared_plugins() {
+            let name = PluginSpec::name_of(&declared);
+            if name == plugin_name {
+                continue;
+            }
+            match plugins::resolve(self.repo, std::slice::from_ref(&declared)) {
+                Ok(closure) => {
+                    if closure.iter().any(|d| d.name == plugin_name) {
+                        dependents.push(name.to_string());
+                    }
+                }
+                Err(e) => warn!("Cannot resolve dependencies of {}: {}", name, e),
+            }
         }
+        dependents
     }
 
-    pub fn remove_plugin(&mut self, plugin: plugins::Definition) -> BoxResult {
-        if !self.has_plugin(&plugin.name) {
+    /// Remove a plugin from the project. When `cascade` is set, any declared plugins that depend on it
+    /// are removed as well, otherwise removing a plugin that is still required is an error.
+    pub fn remove_plugin(&mut self, plugin: plugins::Definition, cascade: bool) -> BoxResult {
+        let declared = match &self.package {
+            Some(config) => config.declared_names().contains(&plugin.name.as_str()),
+            None => false,
+        };
+        if !declared {
             return Err("❗ Plugin doesnt exists in project".into());
         }
 
+        let dependents = self.reverse_dependencies(&plugin.name);
+        if !dependents.is_empty() && !cascade {
+            return Err(format!(
+                "❗ Plugin {} is required by: {}. Use --cascade to remove them as well",
+                plugin.name,
+                dependents.join(", ")
+            )
+            .into());
+        }
+
         match &mut self.package {
             Some(config) => {
-                config.plugins.retain(|p| p != &plugin.name);
+                let removed = |p: &String| {
+                    let name = PluginSpec::name_of(p);
+                    name == plugin.name || dependents.iter().any(|d| d == name)
+                };
+                config.plugins.retain(|p| !removed(p));
+                config.dependencies.retain(|p| !removed(p));
+                for dependent in &dependents {
+                    info!("Plugin Removed {} (depends on {})", dependent, plugin.name);
+                }
                 info!("Plugin Removed {}", plugin.name);
-                Ok(())
             }
-            None => Err("❗ No plugin found?".into()),
+            None => return Err("❗ No plugin found?".into()),
         }
+
+        if let Ok(unused) = self.unused_dependencies()
+            && !unused.is_empty()
+        {
+            info!("Some dependencies are no longer required, run prune to remove them");
+        }
+
+        Ok(())
     }
 
-    pub fn add_plugin(&mut self, plugin: plugins::Definition) -> BoxResult {
-        if self.has_plugin(&plugin.name) {
+    /// Add a plugin to the project along with any dependencies that are not yet declared. The
+    /// plugin may carry a version requirement, eg: `name@^1.2`, which is kept in the plugin list.
+    /// Plugins which, or whose dependencies, do not support the project's game are refused.
+    pub fn add_plugin(&mut self, spec: &str) -> BoxResult {
+        let parsed = PluginSpec::parse(spec)?;
+        if self.has_plugin(parsed.name) {
             return Err("❗ Plugin already exists".into());
         }
+        let closure = plugins::resolve(self.repo, &[spec.to_string()])?;
         match &mut self.package {
             Some(config) => {
-                config.plugins.push(plugin.name.to_lowercase());
+                plugins::check_games(&closure, config.game)?;
+                let name = parsed.name.to_lowercase();
+                // Explicitly adding a plugin that was previously pulled in as a dependency promotes it.
+                config
+                    .dependencies
+                    .retain(|p| PluginSpec::name_of(p) != name);
+                match spec.split_once('@') {
+                    Some((_, requirement)) => {
+                        config.plugins.push(format!("{}@{}", name, requirement))
+                    }
+                    None => config.plugins.push(name.clone()),
+                }
+                for dep in closure {
+                    if dep.name == name || config.declared_names().contains(&dep.name.as_str()) {
+                        continue;
+                    }
+                    info!("Adding dependency {}", dep.name);
+                    config.dependencies.push(dep.name);
+                }
                 Ok(())
             }
             None => Err("❗ No config?".into()),
         }
     }
 
+    /// Move the version requirement of declared plugins forward when the index has a version
+    /// they no longer accept. Only plugins in `names` are touched, or every plugin when it is
+    /// empty. Returns the old and new spec of every plugin that changed.
+    pub fn bump_requirements(&mut self, names: &[String]) -> BoxResult<Vec<(String, String)>> {
+        let repo = self.repo;
+        let package = match &mut self.package {
+            Some(package) => package,
+            None => return Err("No package loaded".into()),
+        };
+        let mut bumped = Vec::new();
+        for spec in package
+            .plugins
+            .iter_mut()
+            .chain(package.dependencies.iter_mut())
+        {
+            let parsed = PluginSpec::parse(spec)?;
+            if !names.is_empty() && !names.iter().any(|n| n == parsed.name) {
+                continue;
+            }
+            let Some(requirement) = &parsed.requirement else {
+                continue;
+            };
+            let definition = repo.find_plugin_definition(parsed.name)?;
+            if requirement.matches(&definition.version) {
+                continue;
+            }
+            let new = format!(
+                "{}@{}",
+                parsed.name,
+                VersionRequirement::compatible_with(&definition.version)
+            );
+            bumped.push((spec.clone(), new.clone()));
+            *spec = new;
+        }
+        Ok(bumped)
+    }
+
+    /// Dependencies which are no longer required by any explicitly declared plugin.
+    pub fn unused_dependencies(&self) -> BoxResult<Vec<String>> {
+        let pkg = match &self.package {
+            Some(pkg) => pkg,
+            None => return Err("No package loaded".into()),
+        };
+        let required = plugins::resolve(self.repo, &pkg.plugins)?;
+        Ok(pkg
+            .dependencies
+            .iter()
+            .filter(|dep| !required.iter().any(|d| d.name == PluginSpec::name_of(dep)))
+            .cloned()
+            .collect())
+    }
+
+    /// Remove dependencies that nothing uses any more, returning their names.
+    pub fn prune_dependencies(&mut self) -> BoxResult<Vec<String>> {
+        let unused = self.unused_dependencies()?;
+        if let Some(config) = &mut self.package {
+            config.dependencies.retain(|p| !unused.contains(p));
+        }
+        Ok(unused)
+    }
+
     fn existing_project(&mut self) -> BoxResult {
-        let file = File::open(self.project_file_path())?;
-        let existing_config: Package = serde_yaml::from_reader(file)?;
+        let layers = layers::load(&self.project_file_path())?;
+        let merged = layers::merge(&layers);
+        let existing_config: Package = match serde_yaml::from_value(merged) {
+            Ok(config) => config,
+            Err(e) => {
+                return Err(format!(
+                    "❗ Failed to read {}: {}. Run `sm-pkg check` for details",
+                    PROJECT_FILE, e
+                )
+                .into());
+            }
+        };
         debug!(
             "Existing project found! (game: {:?})",
             existing_config.game.to_string()
         );
+        if let Some((local, bases)) = layers.split_last()
+            && !bases.is_empty()
+        {
+            self.layered = Some((layers::merge(bases), local.document.clone()));
+        }
         self.package = Some(existing_config);
         Ok(())
     }
 
-    fn create_package_config(&mut self) -> BoxResult {
-        let branch_opts = vec![sdk::Branch::Stable, sdk::Branch::Dev];
-        let branch: Result<sdk::Branch, InquireError> =
-            Select::new("👇 Select a metamod/sourcemod branch", branch_opts).prompt();
-        let options: Vec<Game> = vec![Game::TF];
-        let game: Result<Game, InquireError> = Select::new("👇 Select a game", options).prompt();
-        self.package = match game {
-            Ok(choice) => Some(Package {
-                branch: branch?,
-                game: choice,
-                plugins: Vec::new(),
-                templates: None,
-                description: None,
-                raw_configs: None,
-                create_startup_script: None,
-                startup_opts: None,
-                plugin_configs: None,
-            }),
-            Err(_) => return Err("❗ Failed to select a game".into()),
+    fn create_package_config(
+        &mut self,
+        options: &InitOptions,
+        prompter: &dyn Prompter,
+    ) -> BoxResult {
+        let branch = match &options.branch {
+            Some(branch) => branch.clone(),
+            None if options.yes => sdk::Branch::default(),
+            None => {
+                let branch_opts = vec![sdk::Branch::Stable, sdk::Branch::Dev];
+                select(
+                    prompter,
+                    "👇 Select a metamod/sourcemod branch",
+                    "--branch",
+                    branch_opts,
+                )?
+            }
         };
+        let game = match options.game {
+            Some(game) => game,
+            None if options.yes => Game::default(),
+            None => select(prompter, "👇 Select a game", "--game", Game::all())?,
+        };
+        self.package = Some(Package {
+            extends: None,
+            branch,
+            sourcemod_version: None,
+            metamod_version: None,
+            game,
+            plugins: Vec::new(),
+            dependencies: Vec::new(),
+            mod_folder: None,
+            templates: None,
+            description: None,
+            raw_configs: None,
+            create_startup_script: None,
+            startup_opts: None,
+            plugin_configs: None,
+            overlays: None,
+        });
+        for plugin in &options.plugins {
+            self.add_plugin(plugin)?;
+        }
         self.save_package_config()?;
+        if let Some(pkg) = &self.package {
+            let info = pkg.game.info();
+            if let Some(app_id) = info.app_id {
+                info!(
+                    "Install the {} dedicated server with: steamcmd +login anonymous +force_install_dir {} +app_update {} validate +quit",
+                    info.name,
+                    self.project_root.display(),
+                    app_id
+                );
+            }
+        }
         Ok(())
     }
 
-    pub fn write_configs(&self) -> BoxResult {
+    /// Generated configuration files that have been edited by hand since they were last written.
+    pub fn edited_configs(&self) -> BoxResult<Vec<PathBuf>> {
+        let manifest = Manifest::open(self.project_root)?;
+        Ok(manifest
+            .edited_generated(self.project_root)?
+            .into_iter()
+            .map(|f| f.path.clone())
+            .collect())
+    }
+
+    /// Unified diffs of every local edit made to generated configuration files.
+    pub fn config_diffs(&self) -> BoxResult<Vec<String>> {
+        let manifest = Manifest::open(self.project_root)?;
+        let mut diffs = Vec::new();
+        for file in manifest.edited_generated(self.project_root)? {
+            diffs.push(manifest.diff_generated(self.project_root, file)?);
+        }
+        Ok(diffs)
+    }
+
+    /// Fail if any generated configuration file has been edited by hand, unless `force` is set.
+    pub fn check_config_edits(&self, force: bool) -> BoxResult {
+        let edited = self.edited_configs()?;
+        if edited.is_empty() {
+            return Ok(());
+        }
+        if force {
+            for path in &edited {
+                warn!("Overwriting local edits to {}", path.display());
+            }
+            return Ok(());
+        }
+        for path in &edited {
+            warn!("{} was edited since it was generated", path.display());
+        }
+        Err(format!(
+            "❗ {} generated files have local edits. Review them with `sm-pkg config --diff` or use --force to overwrite",
+            edited.len()
+        )
+        .into())
+    }
+
+    /// Write every generated configuration file, recording their hashes in the install manifest.
+    /// Files edited by hand since they were last written are only overwritten when `force` is set.
+    pub fn write_configs(&self, force: bool) -> BoxResult {
+        self.check_config_edits(force)?;
+        let stage = Stage::new(self.project_root)?;
+        let previous = Manifest::open(self.project_root)?;
+        let mut manifest = Manifest {
+            generated: Vec::new(),
+            ..previous.clone()
+        };
+        self.stage_configs(stage.root(), &mut manifest)?;
+        let stale = manifest.stale(&previous);
+        manifest.save(stage.root())?;
+        stage.commit(&stale)
+    }
+
+    /// Write every generated config below `root`, a directory mirroring the project root such as
+    /// [`Stage::root`], recording each file in `manifest`. Callers are responsible for
+    /// checking for local edits first, see [`Self::check_config_edits`].
+    pub fn stage_configs(&self, root: &Path, manifest: &mut Manifest) -> BoxResult {
         let pkg = match &self.package {
             None => return Err("No package loaded".into()),
             Some(pkg) => pkg,
         };
+        let writer = &mut ConfigWriter {
+            project_root: self.project_root,
+            root,
+            manifest,
+        };
         if let Some(configs) = &pkg.templates {
-            self.write_sourcemod_cfg(&configs.sourcemod_cfg)?;
-            self.write_core_cfg(&configs.core_cfg)?;
-            self.write_databases_cfg(&configs.databases_cfg)?;
-            self.write_maplists_cfg(&configs.maplists_cfg)?;
-            self.write_admins_cfg(&configs.admins_cfg)?;
-            self.write_admin_groups_cfg(&configs.admin_groups_cfg)?;
-            self.write_admin_overrides_cfg(&configs.admin_overrides_cfg)?;
-            self.write_admins_simple_ini(&configs.admins_simple_ini)?;
+            self.write_sourcemod_cfg(writer, &configs.sourcemod_cfg)?;
+            self.write_core_cfg(writer, &configs.core_cfg)?;
+            self.write_databases_cfg(writer, &configs.databases_cfg)?;
+            self.write_maplists_cfg(writer, &configs.maplists_cfg)?;
+            self.write_admins_cfg(writer, &configs.admins_cfg)?;
+            self.write_admin_groups_cfg(writer, &configs.admin_groups_cfg)?;
+            self.write_admin_overrides_cfg(writer, &configs.admin_overrides_cfg)?;
+            self.write_admins_simple_ini(writer, &configs.admins_simple_ini)?;
         };
 
         if let Some(raw_configs) = &pkg.raw_configs {
-            self.write_raw_configs(raw_configs)?;
+            self.write_raw_configs(writer, raw_configs)?;
         }
 
         if let Some(create) = pkg.create_startup_script
             && create
         {
-            self.write_startup_script(&pkg.startup_opts)?
+            self.write_startup_script(writer, pkg)?
         }
 
-        for plugin in &pkg.plugins {
-            let def = self.repo.find_plugin_definition(plugin)?;
-            self.write_plugin_config(&def)?;
+        for def in plugins::resolve(self.repo, &pkg.declared_plugins())? {
+            self.write_plugin_config(writer, &def)?;
         }
 
         Ok(())
     }
 
-    fn write_plugin_config(&self, def: &plugins::Definition) -> BoxResult {
+    fn write_plugin_config(
+        &self,
+        writer: &mut ConfigWriter,
+        def: &plugins::Definition,
+    ) -> BoxResult {
         let mut base_configs = match &def.configs {
             Some(configs) => configs.clone(),
             None => return Ok(()),
@@ -403,47 +705,67 @@ impl<'a> Project<'a> {
                 }
             }
 
-            self.write_raw_configs(&vec![base_config.clone()])?;
+            self.write_raw_configs(writer, &vec![base_config.clone()])?;
         }
 
         Ok(())
     }
 
-    fn write_startup_script(&self, config: &Option<templates::StartSh>) -> BoxResult {
+    fn write_startup_script(&self, writer: &mut ConfigWriter, pkg: &Package) -> BoxResult {
         let script_path = self.project_root.join("start.sh");
-        match &config {
+        match &pkg.startup_opts {
             None => Err("No startup_opts definition found".into()),
-            Some(template) => match write_cfg(&TagFormat::Shell, &script_path, template) {
+            Some(opts) => match writer.write_cfg(
+                &TagFormat::Shell,
+                &script_path,
+                opts.with_game_defaults(pkg.game.info(), pkg.mod_folder())?,
+            ) {
                 Err(e) => Err(e),
                 Ok(()) => {
-                    let mut perms = fs::metadata(&script_path)?.permissions();
+                    let written = writer.out_path(&script_path)?;
+                    let mut perms = fs::metadata(&written)?.permissions();
                     perms.set_mode(0o755);
-                    fs::set_permissions(&script_path, perms)?;
+                    fs::set_permissions(&written, perms)?;
                     Ok(())
                 }
             },
         }
     }
 
-    fn write_raw_configs(&self, raw_configs: &Vec<SimpleConfig>) -> BoxResult {
+    fn write_raw_configs(
+        &self,
+        writer: &mut ConfigWriter,
+        raw_configs: &Vec<SimpleConfig>,
+    ) -> BoxResult {
         for raw_config in raw_configs {
             let out_path = self.project_root.join(&raw_config.path);
-            let mut file = File::create(&out_path)?;
-            write_tag(&TagFormat::Ini, &mut file)?;
+            let mut body = Vec::new();
             for (key, value) in &raw_config.options {
-                writeln!(file, "{} \"{}\"", key, value)?;
+                writeln!(body, "{} \"{}\"", key, value)?;
             }
-            info!("Created {}", out_path.display());
+            writer.write_file(&TagFormat::Ini, &out_path, &body)?;
         }
 
         Ok(())
     }
 
-    fn write_sourcemod_cfg(&self, config: &Option<templates::SourcemodCfg>) -> BoxResult {
+    /// Path to a file inside the mod folder, eg: addons/sourcemod/configs/core.cfg -> tf/addons/sourcemod/configs/core.cfg
+    fn mod_path(&self, path: impl AsRef<Path>) -> BoxResult<PathBuf> {
+        match &self.package {
+            Some(pkg) => Ok(self.project_root.join(pkg.mod_folder()).join(path)),
+            None => Err("No package loaded".into()),
+        }
+    }
+
+    fn write_sourcemod_cfg(
+        &self,
+        writer: &mut ConfigWriter,
+        config: &Option<templates::SourcemodCfg>,
+    ) -> BoxResult {
         if let Some(template) = &config {
-            write_cfg(
+            writer.write_cfg(
                 &TagFormat::Ini,
-                &self.project_root.join("tf/cfg/sourcemod/sourcemod.cfg"),
+                &self.mod_path("cfg/sourcemod/sourcemod.cfg")?,
                 template,
             )?;
         }
@@ -451,13 +773,15 @@ impl<'a> Project<'a> {
         Ok(())
     }
 
-    fn write_core_cfg(&self, config: &Option<templates::CoreCfg>) -> BoxResult {
+    fn write_core_cfg(
+        &self,
+        writer: &mut ConfigWriter,
+        config: &Option<templates::CoreCfg>,
+    ) -> BoxResult {
         if let Some(template) = &config {
-            write_cfg(
+            writer.write_cfg(
                 &TagFormat::Ini,
-                &self
-                    .project_root
-                    .join("tf/addons/sourcemod/configs/core.cfg"),
+                &self.mod_path("addons/sourcemod/configs/core.cfg")?,
                 template,
             )?;
         }
@@ -465,13 +789,15 @@ impl<'a> Project<'a> {
         Ok(())
     }
 
-    fn write_databases_cfg(&self, config: &Option<templates::DatabasesCfg>) -> BoxResult {
+    fn write_databases_cfg(
+        &self,
+        writer: &mut ConfigWriter,
+        config: &Option<templates::DatabasesCfg>,
+    ) -> BoxResult {
         if let Some(template) = &config {
-            write_cfg(
+            writer.write_cfg(
                 &TagFormat::Ini,
-                &self
-                    .project_root
-                    .join("tf/addons/sourcemod/configs/databases.cfg"),
+                &self.mod_path("addons/sourcemod/configs/databases.cfg")?,
                 template,
             )?
         }
@@ -479,13 +805,15 @@ impl<'a> Project<'a> {
         Ok(())
     }
 
-    fn write_maplists_cfg(&self, config: &Option<templates::MaplistsCfg>) -> BoxResult {
+    fn write_maplists_cfg(
+        &self,
+        writer: &mut ConfigWriter,
+        config: &Option<templates::MaplistsCfg>,
+    ) -> BoxResult {
         if let Some(template) = &config {
-            write_cfg(
+            writer.write_cfg(
                 &TagFormat::Ini,
-                &self
-                    .project_root
-                    .join("tf/addons/sourcemod/configs/maplists.cfg"),
+                &self.mod_path("addons/sourcemod/configs/maplists.cfg")?,
                 template,
             )?;
         }
@@ -493,13 +821,15 @@ impl<'a> Project<'a> {
         Ok(())
     }
 
-    fn write_admins_simple_ini(&self, config: &Option<templates::AdminsSimpleIni>) -> BoxResult {
+    fn write_admins_simple_ini(
+        &self,
+        writer: &mut ConfigWriter,
+        config: &Option<templates::AdminsSimpleIni>,
+    ) -> BoxResult {
         if let Some(template) = &config {
-            write_cfg(
+            writer.write_cfg(
                 &TagFormat::Ini,
-                &self
-                    .project_root
-                    .join("tf/addons/sourcemod/configs/admins_simple.ini"),
+                &self.mod_path("addons/sourcemod/configs/admins_simple.ini")?,
                 template,
             )?
         }
@@ -507,13 +837,15 @@ impl<'a> Project<'a> {
         Ok(())
     }
 
-    fn write_admins_cfg(&self, config: &Option<templates::AdminsCfg>) -> BoxResult {
+    fn write_admins_cfg(
+        &self,
+        writer: &mut ConfigWriter,
+        config: &Option<templates::AdminsCfg>,
+    ) -> BoxResult {
         if let Some(template) = &config {
-            write_cfg(
+            writer.write_cfg(
                 &TagFormat::Ini,
-                &self
-                    .project_root
-                    .join("tf/addons/sourcemod/configs/admins.cfg"),
+                &self.mod_path("addons/sourcemod/configs/admins.cfg")?,
                 template,
             )?
         }
@@ -521,13 +853,15 @@ impl<'a> Project<'a> {
         Ok(())
     }
 
-    fn write_admin_groups_cfg(&self, config: &Option<templates::AdminGroupsCfg>) -> BoxResult {
+    fn write_admin_groups_cfg(
+        &self,
+        writer: &mut ConfigWriter,
+        config: &Option<templates::AdminGroupsCfg>,
+    ) -> BoxResult {
         if let Some(template) = &config {
-            write_cfg(
+            writer.write_cfg(
                 &TagFormat::Ini,
-                &self
-                    .project_root
-                    .join("tf/addons/sourcemod/configs/admin_groups.cfg"),
+                &self.mod_path("addons/sourcemod/configs/admin_groups.cfg")?,
                 template,
             )?
         }
@@ -537,14 +871,13 @@ impl<'a> Project<'a> {
 
     fn write_admin_overrides_cfg(
         &self,
+        writer: &mut ConfigWriter,
         config: &Option<templates::AdminOverridesCfg>,
     ) -> BoxResult {
         if let Some(template) = &config {
-            write_cfg(
+            writer.write_cfg(
                 &TagFormat::Ini,
-                &self
-                    .project_root
-                    .join("tf/addons/sourcemod/configs/admin_overrides.cfg"),
+                &self.mod_path("addons/sourcemod/configs/admin_overrides.cfg")?,
                 template,
             )?
         }
@@ -553,6 +886,34 @@ impl<'a> Project<'a> {
     }
 }
 
+/// Overlays declared by the project in `project_root`, including extended files. Read on their
+/// own as they are needed to find plugins before the project itself can be opened.
+pub fn declared_overlays(project_root: &Path) -> BoxResult<Vec<OverlaySource>> {
+    let layers = layers::load(&project_root.join(PROJECT_FILE))?;
+    match layers::merge(&layers).get("overlays") {
+        Some(overlays) => match serde_yaml::from_value(overlays.clone()) {
+            Ok(overlays) => Ok(overlays),
+            Err(e) => Err(format!("❗ Invalid overlays in {}: {}", PROJECT_FILE, e).into()),
+        },
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Ask `prompter` to choose one of `options`, pointing at `flag` if it can't.
+fn select<T: Display>(
+    prompter: &dyn Prompter,
+    message: &str,
+    flag: &str,
+    mut options: Vec<T>,
+) -> BoxResult<T> {
+    let labels: Vec<String> = options.iter().map(|o| o.to_string()).collect();
+    match prompter.select(message, &labels) {
+        Ok(index) if index < options.len() => Ok(options.swap_remove(index)),
+        Ok(_) => Err("❗ Invalid selection".into()),
+        Err(e) => Err(format!("{}, pass {} or --yes instead", e, flag).into()),
+    }
+}
+
 enum TagFormat {
     Shell,
     Ini,
@@ -574,14 +935,209 @@ fn write_tag(format: &TagFormat, fp: &mut impl std::io::Write) -> BoxResult {
     Ok(())
 }
 
-fn write_cfg(format: &TagFormat, path: &Path, template: impl Template) -> BoxResult {
-    let mut fp = File::create(path)?;
-    write_tag(format, &mut fp)?;
-    match Template::write_into(&template, &mut fp) {
-        Ok(()) => {
-            info!("Created {}", path.display());
-            Ok(())
-        }
-        Err(err) => Err(err.into()),
+fn create_parent_dirs(path: &Path) -> BoxResult {
+    if let Some(parent) = path.parent()
+        && !parent.exists()
+    {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(())
+}
+
+/// Writes generated files, recording what was written in the install manifest. Paths are given
+/// under the project root but written to the same place under `root`.
+struct ConfigWriter<'m> {
+    project_root: &'m Path,
+    root: &'m Path,
+    manifest: &'m mut Manifest,
+}
+
+impl ConfigWriter<'_> {
+    fn write_cfg(&mut self, format: &TagFormat, path: &Path, template: impl Template) -> BoxResult {
+        let mut body = Vec::new();
+        Template::write_into(&template, &mut body)?;
+        self.write_file(format, path, &body)
+    }
+
+    /// Where a file under the project root is actually written.
+    fn out_path(&self, path: &Path) -> BoxResult<PathBuf> {
+        Ok(self.root.join(path.strip_prefix(self.project_root)?))
+    }
+
+    fn write_file(&mut self, format: &TagFormat, path: &Path, body: &[u8]) -> BoxResult {
+        let out_path = self.out_path(path)?;
+        create_parent_dirs(&out_path)?;
+        let mut fp = File::create(&out_path)?;
+        write_tag(format, &mut fp)?;
+        fp.write_all(body)?;
+        drop(fp);
+        self.manifest.record_generated(self.root, &out_path)?;
+        info!("Created {}", path.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{TestRepo, write};
+
+    fn repo() -> TestRepo {
+        TestRepo::new(&[
+            ("app", "1.0.0", &["lib"]),
+            ("lib", "1.0.0", &["base"]),
+            ("base", "1.0.0", &[]),
+            ("tool", "1.0.0", &[]),
+        ])
+    }
+
+    /// Open a project whose sm-pkg.yaml declares `plugins` and `dependencies`.
+    fn open<'a>(
+        root: &'a &Path,
+        repo: &'a TestRepo,
+        plugins: &[&str],
+        dependencies: &[&str],
+    ) -> Project<'a> {
+        write(
+            &root.join(PROJECT_FILE),
+            &format!(
+                "game: TF\nbranch: Stable\nplugins: {:?}\ndependencies: {:?}\n",
+                plugins, dependencies
+            ),
+        );
+        let mut project = Project::new(root, repo).unwrap();
+        project.open().unwrap();
+        project
+    }
+
+    fn declared(project: &Project) -> (Vec<String>, Vec<String>) {
+        let package = project.package.as_ref().unwrap();
+        (package.plugins.clone(), package.dependencies.clone())
+    }
+
+    #[test]
+    fn add_declares_missing_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        let (root, repo) = (dir.path(), repo());
+        let mut project = open(&root, &repo, &["tool"], &[]);
+        project.add_plugin("app@^1").unwrap();
+        assert_eq!(
+            declared(&project),
+            (
+                vec!["tool".to_string(), "app@^1".to_string()],
+                vec!["base".to_string(), "lib".to_string()]
+            )
+        );
+        assert!(project.add_plugin("app").is_err());
+    }
+
+    #[test]
+    fn add_promotes_a_dependency_to_a_plugin() {
+        let dir = tempfile::tempdir().unwrap();
+        let (root, repo) = (dir.path(), repo());
+        let mut project = open(&root, &repo, &["app"], &["lib", "base"]);
+        project.add_plugin("lib").unwrap();
+        assert_eq!(
+            declared(&project),
+            (
+                vec!["app".to_string(), "lib".to_string()],
+                vec!["base".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn remove_refuses_while_a_dependent_needs_the_plugin() {
+        let dir = tempfile::tempdir().unwrap();
+        let (root, repo) = (dir.path(), repo());
+        let mut project = open(&root, &repo, &["app", "tool"], &["lib", "base"]);
+        assert_eq!(project.reverse_dependencies("base"), vec!["app", "lib"]);
+        let base = repo.find_plugin_definition("base").unwrap();
+        let err = project.remove_plugin(base, false).unwrap_err();
+        assert!(err.to_string().contains("required by: app, lib"), "{}", err);
+        assert_eq!(declared(&project).0, vec!["app", "tool"]);
+    }
+
+    #[test]
+    fn remove_cascades_to_dependents() {
+        let dir = tempfile::tempdir().unwrap();
+        let (root, repo) = (dir.path(), repo());
+        let mut project = open(&root, &repo, &["app", "tool"], &["lib", "base"]);
+        let lib = repo.find_plugin_definition("lib").unwrap();
+        project.remove_plugin(lib, true).unwrap();
+        assert_eq!(
+            declared(&project),
+            (vec!["tool".to_string()], vec!["base".to_string()])
+        );
+    }
+
+    #[test]
+    fn prune_removes_only_unreferenced_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        let (root, repo) = (dir.path(), repo());
+        let mut project = open(&root, &repo, &["app"], &["lib", "base", "tool"]);
+        assert_eq!(project.prune_dependencies().unwrap(), vec!["tool"]);
+        assert_eq!(declared(&project).1, vec!["lib", "base"]);
+        assert!(project.prune_dependencies().unwrap().is_empty());
+    }
+
+    #[test]
+    fn write_configs_removes_files_no_longer_generated() {
+        let dir = tempfile::tempdir().unwrap();
+        let (root, repo) = (dir.path(), repo());
+        let project = open(&root, &repo, &["tool"], &[]);
+        let old = root.join("cfg/sourcemod/old.cfg");
+        write(&old, "// removed template");
+        let mut manifest = Manifest::default();
+        manifest.record_generated(root, &old).unwrap();
+        manifest.save(root).unwrap();
+
+        project.write_configs(false).unwrap();
+        assert!(!old.exists());
+        assert!(
+            !crate::manifest::generated_copy_path(root, Path::new("cfg/sourcemod/old.cfg"))
+                .exists()
+        );
+        assert!(Manifest::open(root).unwrap().generated.is_empty());
+    }
+
+    /// Open a project generating tf/cfg/server.cfg and write its configs once.
+    fn generated_project<'a>(root: &'a &Path, repo: &'a TestRepo) -> Project<'a> {
+        let mut project = open(root, repo, &["tool"], &[]);
+        project.package.as_mut().unwrap().raw_configs = Some(vec![SimpleConfig {
+            path: PathBuf::from("tf/cfg/server.cfg"),
+            options: HashMap::from([("hostname".to_string(), "sm-pkg".to_string())]),
+        }]);
+        project.write_configs(false).unwrap();
+        project
+    }
+
+    #[test]
+    fn write_configs_refuses_to_overwrite_local_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let (root, repo) = (dir.path(), repo());
+        let project = generated_project(&root, &repo);
+        let cfg = root.join("tf/cfg/server.cfg");
+        let generated = fs::read_to_string(&cfg).unwrap();
+        project.check_config_edits(false).unwrap();
+
+        write(&cfg, "hostname \"edited\"\n");
+        assert_eq!(
+            project.edited_configs().unwrap(),
+            vec![PathBuf::from("tf/cfg/server.cfg")]
+        );
+        let err = project.write_configs(false).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("1 generated files have local edits"),
+            "{}",
+            err
+        );
+        assert_eq!(fs::read_to_string(&cfg).unwrap(), "hostname \"edited\"\n");
+        assert!(project.config_diffs().unwrap()[0].contains("+hostname \"edited\""));
+
+        project.write_configs(true).unwrap();
+        assert_eq!(fs::read_to_string(&cfg).unwrap(), generated);
+        assert!(project.edited_configs().unwrap().is_empty());
     }
 }
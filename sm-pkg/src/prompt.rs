@@ -0,0 +1,21 @@
+use crate::BoxResult;
+
+/// Answers the questions asked while creating a new project. The binary provides an interactive
+/// implementation; [`NoPrompt`] is used when nobody is around to answer.
+pub trait Prompter {
+    /// Ask the user to pick one of `options`, returning the index of the chosen option.
+    fn select(&self, message: &str, options: &[String]) -> BoxResult<usize>;
+}
+
+/// Prompter for non-interactive sessions, every question fails.
+pub struct NoPrompt;
+
+impl Prompter for NoPrompt {
+    fn select(&self, message: &str, _options: &[String]) -> BoxResult<usize> {
+        Err(format!(
+            "❗ \"{}\" needs an answer but stdin is not a terminal",
+            message
+        )
+        .into())
+    }
+}
@@ -39,6 +39,7 @@ impl Git {
         remote_callbacks.credentials(auth.credentials(&git_config));
         fetch_options.remote_callbacks(remote_callbacks);
         repo_builder.fetch_options(fetch_options);
+        repo_builder.branch(branch);
 
         let repo = repo_builder.clone(url, path)?;
 
@@ -48,6 +49,19 @@ impl Git {
         })
     }
 
+    /// Open an existing checkout without attempting to clone it.
+    pub fn open(path: &Path, branch: &str) -> Result<Self, git2::Error> {
+        Ok(Self {
+            repo: Repository::open(path)?,
+            branch: branch.to_string(),
+        })
+    }
+
+    /// Commit id currently checked out.
+    pub fn revision(&self) -> Result<String, git2::Error> {
+        Ok(self.repo.head()?.peel_to_commit()?.id().to_string())
+    }
+
     pub fn pull(&self) -> BoxResult {
         self.reset()?;
         let mut remote = self.repo.find_remote(DEFAULT_REMOTE)?;
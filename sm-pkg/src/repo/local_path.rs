@@ -1,23 +1,33 @@
-use std::path::Path;
+use std::{
+    fs::{self, File},
+    path::PathBuf,
+};
 
 use crate::{
     BoxResult, PLUGIN_DEFINITION_FILE,
     plugins::{self},
-    repo::PluginDefinitionProvider,
+    repo::{PluginDefinitionProvider, git},
 };
-pub struct PathOverlay<'a> {
-    root_path: &'a Path,
+
+/// Plugins read straight from a directory laid out like the plugins repository, without an
+/// index: `<root>/<plugin>/plugin.yaml` with sources in `<root>/<plugin>/src`.
+pub struct PathOverlay {
+    root_path: PathBuf,
 }
 
-impl<'a> PathOverlay<'a> {
-    pub fn new(root_path: &'a Path) -> Self {
+impl PathOverlay {
+    pub fn new(root_path: PathBuf) -> Self {
         PathOverlay { root_path }
     }
+
+    fn definition_path(&self, plugin: &str) -> PathBuf {
+        self.root_path.join(plugin).join(PLUGIN_DEFINITION_FILE)
+    }
 }
 
-impl<'a> PluginDefinitionProvider<'a> for PathOverlay<'a> {
+impl<'p> PluginDefinitionProvider<'p> for PathOverlay {
     fn find_plugin_definition(&self, plugin: &str) -> BoxResult<plugins::Definition> {
-        let definition_path = self.root_path.join(plugin).join(PLUGIN_DEFINITION_FILE);
+        let definition_path = self.definition_path(plugin);
         if !definition_path.exists() {
             return Err(format!(
                 "Plugin definition file not found at {}",
@@ -26,10 +36,99 @@ impl<'a> PluginDefinitionProvider<'a> for PathOverlay<'a> {
             .into());
         }
 
-        Err("...".into())
+        let mut definition: plugins::Definition =
+            match serde_yaml::from_reader(File::open(&definition_path)?) {
+                Ok(definition) => definition,
+                Err(e) => {
+                    return Err(
+                        format!("Failed to read {}: {}", definition_path.display(), e).into(),
+                    );
+                }
+            };
+        if definition.name != plugin {
+            return Err(format!(
+                "{} declares plugin {}, it must match the directory name {}",
+                definition_path.display(),
+                definition.name,
+                plugin
+            )
+            .into());
+        }
+        definition.path = Some(self.root_path.join(plugin).join("src").join("scripting"));
+        Ok(definition)
+    }
+
+    fn find_plugin_definitions(&self, plugins: &[String]) -> BoxResult<Vec<plugins::Definition>> {
+        plugins
+            .iter()
+            .map(|plugin| self.find_plugin_definition(plugin))
+            .collect()
+    }
+
+    fn provides(&self, plugin: &str) -> bool {
+        self.definition_path(plugin).exists()
     }
 
-    fn find_plugin_definitions(&self, _plugins: &[String]) -> BoxResult<Vec<plugins::Definition>> {
-        todo!()
+    /// Every readable definition, broken ones are skipped with a warning so they only affect
+    /// lookups of that plugin.
+    fn plugins(&self) -> BoxResult<Vec<plugins::Definition>> {
+        let mut definitions = Vec::new();
+        for entry in fs::read_dir(&self.root_path)? {
+            let entry = entry?;
+            if let Some(name) = entry.file_name().to_str()
+                && self.provides(name)
+            {
+                match self.find_plugin_definition(name) {
+                    Ok(definition) => definitions.push(definition),
+                    Err(e) => warn!("Skipping overlay plugin {}: {}", name, e),
+                }
+            }
+        }
+        definitions.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(definitions)
+    }
+
+    fn plugin_revision(&self, _plugin: &str) -> Option<String> {
+        match git::Git::open(&self.root_path, git::DEFAULT_BRANCH) {
+            Ok(repo) => repo.revision().ok(),
+            Err(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::write;
+    use std::path::Path;
+
+    fn write_definition(root: &Path, dir: &str, contents: &str) {
+        write(&root.join(dir).join(PLUGIN_DEFINITION_FILE), contents);
+    }
+
+    #[test]
+    fn broken_definitions_are_skipped() {
+        let root = tempfile::tempdir().unwrap();
+        write_definition(
+            root.path(),
+            "good",
+            "name: good\ndescription: Works\nversion: 1.0.0\n",
+        );
+        write_definition(
+            root.path(),
+            "renamed",
+            "name: other\ndescription: Wrong name\nversion: 1.0.0\n",
+        );
+        write_definition(root.path(), "broken", "name: [\n");
+        let overlay = PathOverlay::new(root.path().to_path_buf());
+
+        let names: Vec<String> = overlay
+            .plugins()
+            .unwrap()
+            .into_iter()
+            .map(|d| d.name)
+            .collect();
+        assert_eq!(names, vec!["good"]);
+        assert!(overlay.find_plugin_definition("broken").is_err());
     }
 }
@@ -1,8 +1,17 @@
 pub mod git;
 pub mod local_path;
 pub mod overlay;
+pub mod search;
 
-use crate::{BoxResult, INDEX_FILE, REPO_URL, plugins, repo::overlay::PluginOverlays};
+use crate::{
+    BoxResult, INDEX_FILE, PROJECT_FILE, REPO_URL,
+    config::Config,
+    plugins, project,
+    repo::{
+        overlay::{OverlaySource, PluginOverlays},
+        search::SearchFilter,
+    },
+};
 use archive::{ArchiveExtractor, ArchiveFormat};
 use std::{
     fs::{File, create_dir_all, remove_dir_all, write},
@@ -12,6 +21,25 @@ use std::{
 pub trait PluginDefinitionProvider<'a> {
     fn find_plugin_definitions(&self, plugins: &[String]) -> BoxResult<Vec<plugins::Definition>>;
     fn find_plugin_definition(&self, plugin: &str) -> BoxResult<plugins::Definition>;
+    /// Whether the provider has a plugin named `plugin`.
+    fn provides(&self, plugin: &str) -> bool;
+    /// Every plugin the provider has.
+    fn plugins(&self) -> BoxResult<Vec<plugins::Definition>>;
+    /// Commit the sources of `plugin` are taken from, when they are under version control.
+    fn plugin_revision(&self, plugin: &str) -> Option<String>;
+
+    /// Plugins matching `query` and `filter`, most relevant first. An empty query matches every
+    /// plugin passing the filter.
+    fn search(&self, query: &str, filter: &SearchFilter) -> BoxResult<Vec<plugins::Definition>> {
+        Ok(search::rank(self.plugins()?, query, filter))
+    }
+
+    /// Plugins that directly depend on `plugin`.
+    fn dependents(&self, plugin: &str) -> BoxResult<Vec<plugins::Definition>> {
+        let mut packages = self.plugins()?;
+        packages.retain(|p| p.dependency_names().any(|d| d == plugin));
+        Ok(packages)
+    }
 }
 
 pub struct LocalRepo<'a> {
@@ -33,6 +61,14 @@ impl<'a> LocalRepo<'a> {
         self.root
     }
 
+    /// Commit the local repo checkout is at. Snapshots downloaded by [`Self::checkout_repo`] have none.
+    pub fn revision(&self) -> Option<String> {
+        match git::Git::open(&self.root.join("repo"), git::DEFAULT_BRANCH) {
+            Ok(repo) => repo.revision().ok(),
+            Err(_) => None,
+        }
+    }
+
     fn read_index(&self) -> Result<Vec<plugins::Definition>, Box<dyn std::error::Error>> {
         let index = match File::open(self.root.join("repo").join(INDEX_FILE)) {
             Ok(file) => file,
@@ -45,18 +81,6 @@ impl<'a> LocalRepo<'a> {
         Ok(results)
     }
 
-    pub fn search(&self, query: &str) -> BoxResult<Vec<plugins::Definition>> {
-        let mut packages = self.read_index()?;
-        packages.retain(|p| {
-            p.name.to_lowercase().contains(query) || p.description.to_lowercase().contains(query)
-        });
-        if packages.is_empty() {
-            Err("No matches found".into())
-        } else {
-            Ok(packages)
-        }
-    }
-
     // pub async fn build(name: String) {}
     // git clone --no-checkout --depth=1 --filter=tree:0 git@github.com:sm-pkg/plugins plugins
     // git sparse-checkout set --no-cone /connect
@@ -88,24 +112,9 @@ impl<'a> LocalRepo<'a> {
 
         Ok(())
     }
-
-    pub fn plugins(&self) -> BoxResult<Vec<plugins::Definition>> {
-        let mut valid_definitions: Vec<plugins::Definition> = Vec::new();
-        for known_plugin in self.read_index()? {
-            let mut plugin = known_plugin.clone();
-            plugin.path = Some(
-                self.root
-                    .join(format!("repo/{}/src/scripting", &plugin.name))
-                    .to_path_buf(),
-            );
-            valid_definitions.push(plugin);
-        }
-
-        Ok(valid_definitions)
-    }
 }
 
-impl<'a> PluginDefinitionProvider<'a> for LocalRepo<'a> {
+impl<'p> PluginDefinitionProvider<'p> for LocalRepo<'_> {
     fn find_plugin_definitions(&self, plugins: &[String]) -> BoxResult<Vec<plugins::Definition>> {
         let packages = self.read_index()?;
         let mut valid_definitions: Vec<plugins::Definition> = Vec::new();
@@ -150,13 +159,171 @@ impl<'a> PluginDefinitionProvider<'a> for LocalRepo<'a> {
             Some(plugin) => Ok(plugin),
         }
     }
+
+    fn provides(&self, plugin: &str) -> bool {
+        self.read_index()
+            .is_ok_and(|index| index.iter().any(|d| d.name == plugin))
+    }
+
+    fn plugins(&self) -> BoxResult<Vec<plugins::Definition>> {
+        let mut valid_definitions: Vec<plugins::Definition> = Vec::new();
+        for known_plugin in self.read_index()? {
+            let mut plugin = known_plugin.clone();
+            plugin.path = Some(
+                self.root
+                    .join(format!("repo/{}/src/scripting", &plugin.name))
+                    .to_path_buf(),
+            );
+            valid_definitions.push(plugin);
+        }
+
+        Ok(valid_definitions)
+    }
+
+    fn plugin_revision(&self, _plugin: &str) -> Option<String> {
+        self.revision()
+    }
 }
 
-pub fn open_default_overlays(root_path: &'static Path) -> BoxResult<PluginOverlays<'static>> {
+/// The default repository with every overlay from the app root's config.yaml, and the
+/// sm-pkg.yaml in `project_root` when there is one, stacked over it by priority.
+pub fn open<'a>(app_root: &'a Path, project_root: Option<&Path>) -> BoxResult<PluginOverlays<'a>> {
     let mut overlays = PluginOverlays::default();
+    overlays.add_overlay(Box::new(LocalRepo::new(app_root)));
+
+    let mut sources = overlay_sources(app_root, project_root)?;
+    // Stable, so ties keep their declaration order and later overlays end up first.
+    sources.sort_by_key(|(source, _)| source.priority.unwrap_or(0));
+    for (source, base_dir) in sources {
+        overlays.add_overlay(Box::new(source.open(app_root, &base_dir)?));
+    }
+    Ok(overlays)
+}
+
+/// Clone every git overlay used by [`open`] that is not checked out yet.
+pub fn fetch_overlays(app_root: &Path, project_root: Option<&Path>) -> BoxResult {
+    for (source, _) in overlay_sources(app_root, project_root)? {
+        source.fetch(app_root)?;
+    }
+    Ok(())
+}
+
+/// Pull every git overlay used by [`open`].
+pub fn update_overlays(app_root: &Path, project_root: Option<&Path>) -> BoxResult {
+    for (source, _) in overlay_sources(app_root, project_root)? {
+        source.update(app_root)?;
+    }
+    Ok(())
+}
+
+/// Overlays declared in config.yaml followed by those of the project, along with the directory
+/// their relative paths are resolved against.
+fn overlay_sources(
+    app_root: &Path,
+    project_root: Option<&Path>,
+) -> BoxResult<Vec<(OverlaySource, PathBuf)>> {
+    let mut sources: Vec<(OverlaySource, PathBuf)> = Config::open_or_default(app_root)?
+        .overlays
+        .into_iter()
+        .map(|source| (source, app_root.to_path_buf()))
+        .collect();
+    if let Some(project_root) = project_root
+        && project_root.join(PROJECT_FILE).exists()
+    {
+        for source in project::declared_overlays(project_root)? {
+            sources.push((source, project_root.to_path_buf()));
+        }
+    }
+    Ok(sources)
+}
 
-    let local: Box<dyn PluginDefinitionProvider> = Box::new(LocalRepo::new(root_path));
-    overlays.add_overlay(local);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CONFIG_FILE, PLUGIN_DEFINITION_FILE, testutil::write};
 
-    Err("".into())
+    /// Add `plugins` to the overlay directory `dir`, describing each by the directory it is in.
+    fn overlay(dir: &Path, plugins: &[&str]) {
+        for plugin in plugins {
+            write(
+                &dir.join(plugin).join(PLUGIN_DEFINITION_FILE),
+                &format!(
+                    "name: {}\ndescription: {}\nversion: 1.0.0\n",
+                    plugin,
+                    dir.file_name().unwrap().to_string_lossy()
+                ),
+            );
+        }
+    }
+
+    /// Which source each plugin is taken from.
+    fn sources(repo: &PluginOverlays, plugins: &[&str]) -> Vec<String> {
+        plugins
+            .iter()
+            .map(|p| repo.find_plugin_definition(p).unwrap().description)
+            .collect()
+    }
+
+    #[test]
+    fn overlays_are_stacked_by_priority_then_declaration() {
+        let app = tempfile::tempdir().unwrap();
+        let project = tempfile::tempdir().unwrap();
+        write(
+            &app.path().join("repo").join(INDEX_FILE),
+            "- name: shared\n  description: default\n  version: 1.0.0\n\
+             - name: base_only\n  description: default\n  version: 1.0.0\n",
+        );
+        write(
+            &app.path().join(CONFIG_FILE),
+            "overlays:\n  - path: high\n    priority: 5\n  - path: config\n",
+        );
+        write(
+            &project.path().join(PROJECT_FILE),
+            "game: TF\nbranch: Stable\nplugins: []\n\
+             overlays:\n  - path: first\n  - path: second\n",
+        );
+        overlay(&app.path().join("high"), &["shared"]);
+        overlay(
+            &app.path().join("config"),
+            &["shared", "tie", "config_only"],
+        );
+        overlay(&project.path().join("first"), &["shared", "tie"]);
+        overlay(&project.path().join("second"), &["shared", "tie"]);
+
+        let repo = open(app.path(), Some(project.path())).unwrap();
+        assert_eq!(
+            sources(&repo, &["shared", "tie", "config_only", "base_only"]),
+            vec!["high", "second", "config", "default"]
+        );
+        // Plugins hidden by a higher priority source are only listed once, from the winner.
+        let mut listed: Vec<(String, String)> = repo
+            .plugins()
+            .unwrap()
+            .into_iter()
+            .map(|d| (d.name, d.description))
+            .collect();
+        listed.sort();
+        assert_eq!(
+            listed,
+            [
+                ("base_only", "default"),
+                ("config_only", "config"),
+                ("shared", "high"),
+                ("tie", "second"),
+            ]
+            .map(|(name, source)| (name.to_string(), source.to_string()))
+        );
+    }
+
+    #[test]
+    fn git_overlays_are_not_cloned_when_opening() {
+        let app = tempfile::tempdir().unwrap();
+        write(
+            &app.path().join(CONFIG_FILE),
+            "overlays:\n  - git: https://example.invalid/plugins.git\n",
+        );
+        let err = open(app.path(), None).err().unwrap().to_string();
+        assert!(err.contains("is not checked out yet"), "{}", err);
+        assert!(!app.path().join("overlays").exists());
+    }
 }
@@ -1,34 +1,156 @@
-use crate::{BoxResult, plugins, repo::PluginDefinitionProvider};
+use crate::{
+    BoxResult, plugins,
+    repo::{PluginDefinitionProvider, git, local_path::PathOverlay},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
 /// PluginOverlays is a collection of PluginDefinitionProviders, prioritized by order of insertion,
 /// last to first..
 #[derive(Default)]
 pub struct PluginOverlays<'a> {
-    overlays: Vec<Box<dyn PluginDefinitionProvider<'a>>>,
+    overlays: Vec<Box<dyn for<'p> PluginDefinitionProvider<'p> + 'a>>,
 }
 
 impl<'a> PluginOverlays<'a> {
-    pub fn add_overlay(&mut self, overlay: Box<dyn PluginDefinitionProvider<'a>>) {
+    pub fn add_overlay(&mut self, overlay: Box<dyn for<'p> PluginDefinitionProvider<'p> + 'a>) {
         // Inserted at start so that its given first over any previous overlays.
         self.overlays.insert(0, overlay);
     }
+
+    fn provider(&self, plugin: &str) -> Option<&(dyn for<'p> PluginDefinitionProvider<'p> + 'a)> {
+        self.overlays
+            .iter()
+            .find(|overlay| overlay.provides(plugin))
+            .map(|overlay| overlay.as_ref())
+    }
 }
 
-impl<'a> PluginDefinitionProvider<'a> for PluginOverlays<'a> {
-    fn find_plugin_definitions(&self, _plugins: &[String]) -> BoxResult<Vec<plugins::Definition>> {
-        let mut definitions = Vec::new();
+impl<'p> PluginDefinitionProvider<'p> for PluginOverlays<'_> {
+    fn find_plugin_definitions(&self, plugins: &[String]) -> BoxResult<Vec<plugins::Definition>> {
+        plugins
+            .iter()
+            .map(|plugin| self.find_plugin_definition(plugin))
+            .collect()
+    }
+
+    fn find_plugin_definition(&self, plugin: &str) -> BoxResult<plugins::Definition> {
+        match self.provider(plugin) {
+            Some(overlay) => overlay.find_plugin_definition(plugin),
+            None => Err(format!("Plugin not found: {}", plugin).into()),
+        }
+    }
+
+    fn provides(&self, plugin: &str) -> bool {
+        self.provider(plugin).is_some()
+    }
+
+    /// Plugins of every overlay, leaving out those hidden by a higher priority overlay.
+    fn plugins(&self) -> BoxResult<Vec<plugins::Definition>> {
+        let mut definitions: Vec<plugins::Definition> = Vec::new();
         for overlay in &self.overlays {
-            definitions.extend(overlay.find_plugin_definitions(_plugins)?);
+            for plugin in overlay.plugins()? {
+                if !definitions.iter().any(|d| d.name == plugin.name) {
+                    definitions.push(plugin);
+                }
+            }
         }
         Ok(definitions)
     }
 
-    fn find_plugin_definition(&self, plugin: &str) -> BoxResult<plugins::Definition> {
-        for overlay in &self.overlays {
-            if let Ok(def) = overlay.find_plugin_definition(plugin) {
-                return Ok(def);
+    fn plugin_revision(&self, plugin: &str) -> Option<String> {
+        self.provider(plugin)?.plugin_revision(plugin)
+    }
+}
+
+/// An extra source of plugins stacked over the default repository, laid out like it: one
+/// directory per plugin holding its plugin.yaml and src directory. Exactly one of `path` or
+/// `git` must be set.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct OverlaySource {
+    /// Local directory, relative to the directory of the file declaring the overlay.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// Git repository url, cloned into the app root by `install` or `update` and pulled by
+    /// `update`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git: Option<String>,
+    /// Branch of `git` to use. Defaults to master.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// When several overlays provide the same plugin the one with the highest priority wins,
+    /// on a tie project overlays win over config.yaml ones and later entries over earlier ones.
+    /// The default repository is always used last. Defaults to 0.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+}
+
+impl OverlaySource {
+    /// Open the overlay. Git overlays are never cloned here, so commands that only read the
+    /// index stay offline, see [`Self::fetch`]. Relative paths are resolved against `base_dir`.
+    pub fn open(&self, app_root: &Path, base_dir: &Path) -> BoxResult<PathOverlay> {
+        match (&self.path, &self.git) {
+            (Some(path), None) => {
+                let root = base_dir.join(path);
+                if !root.is_dir() {
+                    return Err(
+                        format!("❗ Overlay directory {} does not exist", root.display()).into(),
+                    );
+                }
+                Ok(PathOverlay::new(root.canonicalize()?))
+            }
+            (None, Some(url)) => {
+                let root = self.checkout_dir(app_root, url);
+                if !root.is_dir() {
+                    return Err(format!(
+                        "❗ Overlay {} is not checked out yet, run `sm-pkg install` or \
+                         `sm-pkg update` (with -p for project overlays) to clone it",
+                        url
+                    )
+                    .into());
+                }
+                Ok(PathOverlay::new(root))
+            }
+            _ => Err("❗ Overlays need exactly one of path or git".into()),
+        }
+    }
+
+    /// Clone a git overlay that is not checked out yet, without pulling existing checkouts.
+    /// Path overlays are left alone.
+    pub fn fetch(&self, app_root: &Path) -> BoxResult {
+        if let Some(url) = &self.git {
+            let root = self.checkout_dir(app_root, url);
+            if !root.is_dir() {
+                info!("Cloning overlay {}", url);
+                git::Git::open_or_create(&root, url, self.branch())?;
             }
         }
-        Err(format!("Plugin not found: {}", plugin).into())
+        Ok(())
+    }
+
+    /// Pull the latest changes of a git overlay, cloning it if needed. Path overlays are left
+    /// alone.
+    pub fn update(&self, app_root: &Path) -> BoxResult {
+        if let Some(url) = &self.git {
+            let root = self.checkout_dir(app_root, url);
+            git::Git::open_or_create(&root, url, self.branch())?.pull()?;
+            info!("Updated overlay {}", url);
+        }
+        Ok(())
+    }
+
+    fn branch(&self) -> &str {
+        self.branch.as_deref().unwrap_or(git::DEFAULT_BRANCH)
+    }
+
+    /// Where a git overlay is checked out, one directory per url and branch.
+    fn checkout_dir(&self, app_root: &Path, url: &str) -> PathBuf {
+        let name: String = format!("{}-{}", url, self.branch())
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        app_root.join("overlays").join(name)
     }
 }
@@ -0,0 +1,243 @@
+use crate::{plugins::Definition, project::Game};
+
+/// Restrictions applied to search results on top of the query. Text comparisons ignore case.
+#[derive(Debug, Default)]
+pub struct SearchFilter {
+    /// Part of the name of one of the authors.
+    pub author: Option<String>,
+    pub license: Option<String>,
+    /// Only plugins that support this game.
+    pub game: Option<Game>,
+    pub tag: Option<String>,
+    /// Only plugins that directly depend on this plugin.
+    pub dependency: Option<String>,
+}
+
+impl SearchFilter {
+    pub fn matches(&self, plugin: &Definition) -> bool {
+        let author = self.author.as_ref().is_none_or(|author| {
+            let author = author.to_lowercase();
+            plugin
+                .authors
+                .iter()
+                .flatten()
+                .any(|a| a.to_lowercase().contains(&author))
+        });
+        let license = self.license.as_ref().is_none_or(|license| {
+            plugin
+                .license
+                .as_ref()
+                .is_some_and(|l| l.eq_ignore_ascii_case(license))
+        });
+        let tag = self.tag.as_ref().is_none_or(|tag| {
+            plugin
+                .tags
+                .iter()
+                .flatten()
+                .any(|t| t.eq_ignore_ascii_case(tag))
+        });
+        let dependency = self
+            .dependency
+            .as_ref()
+            .is_none_or(|dependency| plugin.dependency_names().any(|d| d == dependency));
+        author && license && tag && dependency && self.game.is_none_or(|game| plugin.supports(game))
+    }
+}
+
+/// Plugins matching every word of `query` and `filter`, ordered by relevance and then name.
+pub fn rank(plugins: Vec<Definition>, query: &str, filter: &SearchFilter) -> Vec<Definition> {
+    let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    let mut scored: Vec<(u32, Definition)> = plugins
+        .into_iter()
+        .filter(|p| filter.matches(p))
+        .filter_map(|p| {
+            let mut total = 0;
+            for term in &terms {
+                total += relevance(term, &p)?;
+            }
+            Some((total, p))
+        })
+        .collect();
+    scored.sort_by(|(a, a_plugin), (b, b_plugin)| {
+        b.cmp(a).then_with(|| a_plugin.name.cmp(&b_plugin.name))
+    });
+    scored.into_iter().map(|(_, p)| p).collect()
+}
+
+/// How well a single lowercased search term matches `plugin`, None when it does not match at
+/// all. Name matches rank above tags, which rank above the description. Misspelled names still
+/// match, below anything containing the term as written.
+fn relevance(term: &str, plugin: &Definition) -> Option<u32> {
+    let name = plugin.name.to_lowercase();
+    if name == term {
+        return Some(100);
+    }
+    if name.starts_with(term) {
+        return Some(80);
+    }
+    if name.contains(term) {
+        return Some(60);
+    }
+    if plugin
+        .tags
+        .iter()
+        .flatten()
+        .any(|t| t.eq_ignore_ascii_case(term))
+    {
+        return Some(50);
+    }
+    if plugin.description.to_lowercase().contains(term) {
+        return Some(40);
+    }
+    // One typo, such as a swapped pair of letters, per four characters. Capped so a typo match
+    // always scores above an abbreviation.
+    let allowed = term.len().div_ceil(4).min(3);
+    if term.len() >= 3
+        && let Some(distance) = name
+            .split(['_', '-'])
+            .chain([name.as_str()])
+            .map(|part| strsim::osa_distance(term, part))
+            .min()
+            .filter(|distance| *distance <= allowed)
+    {
+        return Some(30 - 5 * distance as u32);
+    }
+    if is_subsequence(term, &name) {
+        return Some(10);
+    }
+    None
+}
+
+/// Whether every character of `term` appears in `name` in order, eg: `clsrst` in
+/// `class_restrict`.
+fn is_subsequence(term: &str, name: &str) -> bool {
+    let mut chars = name.chars();
+    term.chars().all(|c| chars.any(|n| n == c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugin(name: &str, description: &str, extra: &str) -> Definition {
+        serde_yaml::from_str(&format!(
+            "name: {}\ndescription: {}\nversion: 1.0.0\n{}",
+            name, description, extra
+        ))
+        .unwrap()
+    }
+
+    fn index() -> Vec<Definition> {
+        vec![
+            plugin(
+                "mapchooser_extended",
+                "Map votes, works well with rip",
+                "games: [TF, CSTRIKE]\nlicense: GPL-3.0\n",
+            ),
+            plugin(
+                "rip",
+                "Reserved slots",
+                "games: [TF]\nauthors: [Leigh]\nlicense: MIT\ntags: [admin]\n",
+            ),
+            plugin(
+                "class_restrict",
+                "Limit classes per team",
+                "games: [TF]\ndependencies: [rip@^1]\ntags: [competitive]\n",
+            ),
+            plugin("nativevotes", "Native vote menus", "games: [CSTRIKE]\n"),
+        ]
+    }
+
+    fn names(plugins: Vec<Definition>) -> Vec<String> {
+        plugins.into_iter().map(|p| p.name).collect()
+    }
+
+    #[test]
+    fn exact_name_outranks_description() {
+        let results = names(rank(index(), "rip", &SearchFilter::default()));
+        assert_eq!(results, vec!["rip", "mapchooser_extended"]);
+    }
+
+    #[test]
+    fn typos_and_abbreviations_match() {
+        assert_eq!(
+            names(rank(index(), "rpi", &SearchFilter::default())),
+            vec!["rip"]
+        );
+        assert_eq!(
+            names(rank(index(), "clsrst", &SearchFilter::default())),
+            vec!["class_restrict"]
+        );
+        assert!(rank(index(), "zzz", &SearchFilter::default()).is_empty());
+    }
+
+    #[test]
+    fn long_terms_allow_a_bounded_number_of_typos() {
+        assert_eq!(
+            names(rank(
+                index(),
+                "mapchooser_extneded",
+                &SearchFilter::default()
+            )),
+            vec!["mapchooser_extended"]
+        );
+        assert!(
+            rank(
+                index(),
+                "mapchooser_extendedxxxxxxx",
+                &SearchFilter::default()
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn every_term_must_match() {
+        let results = names(rank(index(), "map votes", &SearchFilter::default()));
+        assert_eq!(results, vec!["mapchooser_extended"]);
+    }
+
+    #[test]
+    fn filters_exclude_plugins() {
+        let game = SearchFilter {
+            game: Some(Game::CSTRIKE),
+            ..Default::default()
+        };
+        assert_eq!(
+            names(rank(index(), "", &game)),
+            vec!["mapchooser_extended", "nativevotes"]
+        );
+        assert!(rank(index(), "rip", &game).iter().all(|p| p.name != "rip"));
+
+        let filters = [
+            SearchFilter {
+                author: Some("leigh".to_string()),
+                ..Default::default()
+            },
+            SearchFilter {
+                license: Some("mit".to_string()),
+                ..Default::default()
+            },
+            SearchFilter {
+                tag: Some("ADMIN".to_string()),
+                ..Default::default()
+            },
+        ];
+        for filter in filters {
+            assert_eq!(
+                names(rank(index(), "", &filter)),
+                vec!["rip"],
+                "{:?}",
+                filter
+            );
+        }
+        let dependency = SearchFilter {
+            dependency: Some("rip".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            names(rank(index(), "", &dependency)),
+            vec!["class_restrict"]
+        );
+    }
+}
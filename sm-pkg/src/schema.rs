@@ -0,0 +1,60 @@
+use crate::{BoxResult, SCHEMA_URL, project::Package};
+use schemars::{Schema, generate::SchemaSettings};
+use serde_json::Value;
+
+/// JSON schema describing sm-pkg.yaml, derived from [`Package`] and the config templates it
+/// embeds. Draft 07 is used since that is what yaml-language-server understands best.
+pub fn package_schema() -> Schema {
+    let mut schema = SchemaSettings::draft07()
+        .into_generator()
+        .into_root_schema_for::<Package>();
+    schema.insert("$id".to_string(), SCHEMA_URL.into());
+    schema
+}
+
+/// [`package_schema`] rendered as pretty printed JSON.
+pub fn package_schema_json() -> BoxResult<String> {
+    let mut out = serde_json::to_string_pretty(&package_schema())?;
+    out.push('\n');
+    Ok(out)
+}
+
+/// Append extra accepted values to a string enum schema. schemars does not know about serde
+/// aliases, so types which have them add them back with a `#[schemars(transform)]`.
+pub(crate) fn extend_enum(schema: &mut Schema, values: &[&str]) {
+    if let Some(Value::Array(list)) = schema.get_mut("enum") {
+        list.extend(values.iter().map(|v| Value::from(*v)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_in_schema_is_up_to_date() {
+        assert_eq!(
+            include_str!("../../schema/sm-pkg.json"),
+            package_schema_json().unwrap(),
+            "schema/sm-pkg.json is stale, regenerate it with `sm-pkg schema > schema/sm-pkg.json`"
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let schema = package_schema();
+        assert_eq!(
+            schema.get("additionalProperties"),
+            Some(&Value::Bool(false))
+        );
+
+        let err = serde_yaml::from_str::<Package>(
+            "game: TF\nbranch: Stable\nplugins: []\ntemplates:\n  core_cfg:\n    DisableAutoUpdat: \"yes\"\n",
+        )
+        .map(|_| ())
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("unknown field `DisableAutoUpdat`"), "{}", err);
+        assert!(err.contains("line 6"), "{}", err);
+    }
+}
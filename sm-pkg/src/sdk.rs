@@ -2,11 +2,13 @@ use crate::{BoxResult, DL_CACHE, plugins};
 use flate2::read::GzDecoder;
 use reqwest::Error;
 use resolve_path::PathResolveExt;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashMap,
     fmt::Display,
     fs::{File, create_dir_all, remove_file},
-    io::Write,
+    io::{Read, Write},
     os::unix::fs::symlink,
     path::{self, Path, PathBuf},
     process::Command,
@@ -20,7 +22,8 @@ pub enum Runtime {
     Metamod,
 }
 
-#[derive(clap::ValueEnum, Clone, Debug, Serialize, Default, Deserialize)]
+#[derive(clap::ValueEnum, Clone, Debug, Serialize, Default, Deserialize, JsonSchema)]
+#[schemars(transform = branch_aliases)]
 pub enum Branch {
     #[default]
     #[serde(alias = "stable", alias = "STABLE")]
@@ -29,6 +32,186 @@ pub enum Branch {
     Dev,
 }
 
+fn branch_aliases(schema: &mut schemars::Schema) {
+    crate::schema::extend_enum(schema, &["stable", "STABLE", "dev", "DEV"]);
+}
+
+impl Runtime {
+    /// Prefix of the runtime's build archives.
+    fn archive_prefix(&self) -> &'static str {
+        match self {
+            Runtime::Sourcemod => "sourcemod",
+            Runtime::Metamod => "mmsource",
+        }
+    }
+
+    /// Base url the runtime's builds are published under.
+    fn drop_url(&self) -> &'static str {
+        match self {
+            Runtime::Sourcemod => "https://sm.alliedmods.net/smdrop",
+            Runtime::Metamod => "https://mms.alliedmods.net/mmsdrop",
+        }
+    }
+}
+
+impl Display for Runtime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Runtime::Sourcemod => write!(f, "sourcemod"),
+            Runtime::Metamod => write!(f, "metamod"),
+        }
+    }
+}
+
+/// A single sourcemod or metamod build, eg: 1.12.0-git7210.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Build {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    pub build: u32,
+}
+
+impl Build {
+    /// Parse a build, either on its own, eg: 1.12.0-git7210, or from an archive name, eg:
+    /// sourcemod-1.12.0-git7210-linux.tar.gz.
+    pub fn parse(build: &str) -> BoxResult<Self> {
+        let trimmed = build
+            .trim()
+            .trim_start_matches("sourcemod-")
+            .trim_start_matches("mmsource-")
+            .trim_end_matches(".tar.gz")
+            .trim_end_matches("-linux");
+        let invalid = || format!("Invalid build: {}, expected eg: 1.12.0-git7210", build);
+        let (version, number) = trimmed.split_once("-git").ok_or_else(invalid)?;
+        let parts: Vec<u32> = version
+            .split('.')
+            .map(|p| p.parse::<u32>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid())?;
+        match (parts.as_slice(), number.parse::<u32>()) {
+            (&[major, minor, patch], Ok(build)) => Ok(Build {
+                major,
+                minor,
+                patch,
+                build,
+            }),
+            _ => Err(invalid().into()),
+        }
+    }
+
+    /// Linux archive name of this build for `runtime`.
+    pub fn archive(&self, runtime: &Runtime) -> String {
+        format!("{}-{}-linux.tar.gz", runtime.archive_prefix(), self)
+    }
+}
+
+impl Display for Build {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}-git{}",
+            self.major, self.minor, self.patch, self.build
+        )
+    }
+}
+
+/// Which builds of a runtime a project accepts, eg: `1.12.0-git7210` for an exact build or
+/// `>=1.12.0-git7100, <1.12.0-git7300` for a range. Comparators are separated by commas and all
+/// of them must match.
+#[derive(Debug, Clone)]
+pub struct BuildRequirement {
+    comparators: Vec<(Op, Build)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+}
+
+impl BuildRequirement {
+    pub fn parse(requirement: &str) -> BoxResult<Self> {
+        let mut comparators = Vec::new();
+        for part in requirement.split(',').map(str::trim) {
+            let (op, build) = [
+                (">=", Op::GreaterEq),
+                ("<=", Op::LessEq),
+                (">", Op::Greater),
+                ("<", Op::Less),
+                ("=", Op::Exact),
+            ]
+            .into_iter()
+            .find_map(|(prefix, op)| part.strip_prefix(prefix).map(|rest| (op, rest)))
+            .unwrap_or((Op::Exact, part));
+            comparators.push((op, Build::parse(build)?));
+        }
+        Ok(BuildRequirement { comparators })
+    }
+
+    pub fn matches(&self, build: &Build) -> bool {
+        self.comparators.iter().all(|(op, wanted)| match op {
+            Op::Exact => build == wanted,
+            Op::Greater => build > wanted,
+            Op::GreaterEq => build >= wanted,
+            Op::Less => build < wanted,
+            Op::LessEq => build <= wanted,
+        })
+    }
+
+    /// Newest of `builds` matching the requirement.
+    pub fn newest<'b>(&self, builds: &'b [Build]) -> Option<&'b Build> {
+        builds.iter().filter(|b| self.matches(b)).max()
+    }
+
+    /// The build this requirement pins, when it names exactly one.
+    pub fn exact(&self) -> Option<&Build> {
+        match self.comparators.as_slice() {
+            [(Op::Exact, build)] => Some(build),
+            _ => None,
+        }
+    }
+}
+
+impl Display for BuildRequirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts: Vec<String> = self
+            .comparators
+            .iter()
+            .map(|(op, build)| {
+                let op = match op {
+                    Op::Exact => "=",
+                    Op::Greater => ">",
+                    Op::GreaterEq => ">=",
+                    Op::Less => "<",
+                    Op::LessEq => "<=",
+                };
+                format!("{}{}", op, build)
+            })
+            .collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Linux builds of `runtime` linked from a drop directory listing, oldest first.
+fn parse_listing(runtime: &Runtime, listing: &str) -> Vec<Build> {
+    let prefix = format!("{}-", runtime.archive_prefix());
+    let mut builds: Vec<Build> = listing
+        .match_indices(&prefix)
+        .filter_map(|(start, _)| {
+            let rest = &listing[start..];
+            let end = rest.find("-linux.tar.gz")?;
+            Build::parse(&rest[..end]).ok()
+        })
+        .collect();
+    builds.sort();
+    builds.dedup();
+    builds
+}
+
 impl Display for Branch {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -55,9 +238,10 @@ impl<'a> Manager<'a> {
         game_dir: &PathBuf,
     ) -> BoxResult {
         match runtime {
-            Runtime::Sourcemod => self.install_sourcemod(branch, game_dir).await,
-            Runtime::Metamod => self.install_metamod(branch, game_dir).await,
-        }
+            Runtime::Sourcemod => self.install_sourcemod(branch, game_dir).await?,
+            Runtime::Metamod => self.install_metamod(branch, game_dir).await?,
+        };
+        Ok(())
     }
 
     pub async fn install_sdk(&self, runtime: &Runtime, branch: &Branch) -> BoxResult {
@@ -75,7 +259,10 @@ impl<'a> Manager<'a> {
                 self.install_sourcemod(branch, &out_path).await?;
                 self.activate_sdk(branch)
             }
-            Runtime::Metamod => self.install_metamod(branch, &out_path).await,
+            Runtime::Metamod => {
+                self.install_metamod(branch, &out_path).await?;
+                Ok(())
+            }
         }
     }
 
@@ -108,8 +295,63 @@ impl<'a> Manager<'a> {
         reqwest::get(target).await?.text().await
     }
 
-    async fn fetch_archive(&self, url: String, of: &mut File) -> BoxResult {
-        let body = reqwest::get(url).await?.bytes().await?;
+    /// Every linux build published in a drop directory, eg: 1.12, oldest first.
+    pub async fn list_builds(&self, runtime: &Runtime, version: &str) -> BoxResult<Vec<Build>> {
+        let listing = reqwest::get(format!("{}/{}/", runtime.drop_url(), version))
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        Ok(parse_listing(runtime, &listing))
+    }
+
+    /// Archive name of the build to install for `branch`. Without a requirement this is the
+    /// latest build, an exact requirement is used as is and for a range the newest matching
+    /// build on the branch is picked.
+    pub async fn resolve_build(
+        &self,
+        runtime: &Runtime,
+        branch: &Branch,
+        requirement: Option<&BuildRequirement>,
+    ) -> BoxResult<String> {
+        let requirement = match requirement {
+            None => return self.fetch_latest_build(runtime, branch).await,
+            Some(requirement) => requirement,
+        };
+        if let Some(exact) = requirement.exact() {
+            return Ok(exact.archive(runtime));
+        }
+        let latest = self.fetch_latest_build(runtime, branch).await?;
+        if requirement.matches(&Build::parse(&latest)?) {
+            return Ok(latest);
+        }
+        let version = self.get_sdk_branch_version(runtime, branch);
+        match requirement.newest(&self.list_builds(runtime, version).await?) {
+            Some(build) => Ok(build.archive(runtime)),
+            None => Err(format!(
+                "❗ No {} {} build satisfies {}",
+                runtime, version, requirement
+            )
+            .into()),
+        }
+    }
+
+    /// Archive name of the latest build of `runtime` for `branch`.
+    pub async fn fetch_latest_build(
+        &self,
+        runtime: &Runtime,
+        branch: &Branch,
+    ) -> BoxResult<String> {
+        let latest = match runtime {
+            Runtime::Sourcemod => self.fetch_latest_sourcemod_build(branch).await?,
+            Runtime::Metamod => self.fetch_latest_metamod_build(branch).await?,
+        };
+        Ok(latest.trim().to_string())
+    }
+
+    async fn fetch_archive(&self, url: String, out_path: &Path) -> BoxResult {
+        let body = reqwest::get(url).await?.error_for_status()?.bytes().await?;
+        let mut of = File::create(out_path)?;
         of.write_all(&body[..])?;
         Ok(())
     }
@@ -122,54 +364,113 @@ impl<'a> Manager<'a> {
         Ok(cache_path)
     }
 
-    pub async fn install_sourcemod(&self, branch: &Branch, target_dir: &PathBuf) -> BoxResult {
+    /// Install the latest sourcemod build for the branch, returning the archive name that was used.
+    pub async fn install_sourcemod(
+        &self,
+        branch: &Branch,
+        target_dir: &PathBuf,
+    ) -> BoxResult<String> {
         info!("Fetching latest version... ");
         let latest_version = Self::fetch_latest_sourcemod_build(self, branch).await?;
         info!("Found: {latest_version}");
-        let archive_path = self.ensure_cache_dir()?.join(&latest_version);
-        if !archive_path.exists() {
-            let target = format!(
-                "https://sm.alliedmods.net/smdrop/{}/{}",
-                self.get_sdk_branch_version(&Runtime::Sourcemod, branch),
-                &latest_version
-            );
-            info!("Downlading sourcemod sdk: {target}...");
-            let mut of = File::create(&archive_path)?;
-            self.fetch_archive(target, &mut of).await?;
-        }
-
-        self.extract_archive(&archive_path, target_dir)?;
+        self.install_sourcemod_build(&latest_version, target_dir)
+            .await?;
+        Ok(latest_version)
+    }
 
-        Ok(())
+    /// Install an exact sourcemod build, eg: sourcemod-1.12.0-git7210-linux.tar.gz, returning the files
+    /// that were extracted relative to `target_dir`.
+    pub async fn install_sourcemod_build(
+        &self,
+        archive: &str,
+        target_dir: &PathBuf,
+    ) -> BoxResult<Vec<PathBuf>> {
+        let archive_path = self.cached_archive(&Runtime::Sourcemod, archive).await?;
+        self.extract_archive(&archive_path, target_dir)
     }
 
-    pub async fn install_metamod(&self, branch: &Branch, target_dir: &PathBuf) -> BoxResult {
+    /// Install the latest metamod build for the branch, returning the archive name that was used.
+    pub async fn install_metamod(
+        &self,
+        branch: &Branch,
+        target_dir: &PathBuf,
+    ) -> BoxResult<String> {
         info!("Fetching latest version... ");
         let latest_version = Self::fetch_latest_metamod_build(self, branch).await?;
         info!("Found: {latest_version}");
-        let archive_path = self.ensure_cache_dir()?.join(&latest_version);
+        self.install_metamod_build(&latest_version, target_dir)
+            .await?;
+        Ok(latest_version)
+    }
+
+    /// Install an exact metamod build, eg: mmsource-1.12.0-git1219-linux.tar.gz, returning the files
+    /// that were extracted relative to `target_dir`.
+    pub async fn install_metamod_build(
+        &self,
+        archive: &str,
+        target_dir: &PathBuf,
+    ) -> BoxResult<Vec<PathBuf>> {
+        let archive_path = self.cached_archive(&Runtime::Metamod, archive).await?;
+        self.extract_archive(&archive_path, target_dir)
+    }
+
+    /// Path of an archive in the download cache, downloading it first if it is not there yet.
+    pub async fn cached_archive(&self, runtime: &Runtime, archive: &str) -> BoxResult<PathBuf> {
+        let archive_path = self.ensure_cache_dir()?.join(archive);
         if !archive_path.exists() {
             let target = format!(
-                "https://mms.alliedmods.net/mmsdrop/{}/{}",
-                self.get_sdk_branch_version(&Runtime::Metamod, branch),
-                &latest_version
+                "{}/{}/{}",
+                runtime.drop_url(),
+                archive_branch_version(archive)?,
+                archive
             );
-            info!("Downlading metamod sdk: {target}...");
-            let mut of = File::create(&archive_path)?;
-            self.fetch_archive(target, &mut of).await?;
+            info!("Downlading {} sdk: {target}...", runtime);
+            self.fetch_archive(target, &archive_path).await?;
         }
+        Ok(archive_path)
+    }
 
-        self.extract_archive(&archive_path, target_dir)?;
-
-        Ok(())
+    /// Read the regular files of an archive for which `filter` returns true, keyed by their
+    /// path inside the archive.
+    pub fn read_archive(
+        &self,
+        archive_path: &Path,
+        filter: impl Fn(&Path) -> bool,
+    ) -> BoxResult<HashMap<PathBuf, Vec<u8>>> {
+        let mut archive = Archive::new(GzDecoder::new(File::open(archive_path)?));
+        let mut files = HashMap::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.to_path_buf();
+            if entry.header().entry_type().is_file() && filter(&path) {
+                let mut contents = Vec::new();
+                entry.read_to_end(&mut contents)?;
+                files.insert(path, contents);
+            }
+        }
+        Ok(files)
     }
 
-    fn extract_archive(&self, archive_path: &PathBuf, out_path: &PathBuf) -> BoxResult {
+    /// Unpack an archive into `out_path`, returning the files it contained relative to `out_path`.
+    fn extract_archive(
+        &self,
+        archive_path: &PathBuf,
+        out_path: &PathBuf,
+    ) -> BoxResult<Vec<PathBuf>> {
         info!("Extracting into: {:?}...", out_path);
         let input_archive = File::open(archive_path)?;
         let mut archive = Archive::new(GzDecoder::new(&input_archive));
-        archive.unpack(out_path)?;
-        Ok(())
+        let mut files = Vec::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.to_path_buf();
+            let is_file = entry.header().entry_type().is_file();
+            entry.unpack_in(out_path)?;
+            if is_file {
+                files.push(path);
+            }
+        }
+        Ok(files)
     }
 
     pub fn get_installed_sdks(&self) -> Vec<String> {
@@ -515,6 +816,20 @@ impl Default for CompilerArgs {
     }
 }
 
+/// Extract the major.minor drop directory from an archive name,
+/// eg: sourcemod-1.12.0-git7210-linux.tar.gz -> 1.12
+fn archive_branch_version(archive: &str) -> BoxResult<String> {
+    let version = match archive.split('-').nth(1) {
+        Some(version) => version,
+        None => return Err(format!("Invalid archive name: {archive}").into()),
+    };
+    let parts: Vec<&str> = version.split('.').take(2).collect();
+    if parts.len() != 2 {
+        return Err(format!("Invalid archive version: {archive}").into());
+    }
+    Ok(parts.join("."))
+}
+
 fn default_include_path(sdk_path: &Path) -> BoxResult<PathBuf> {
     let include_path = sdk_path
         .join("addons")
@@ -527,3 +842,83 @@ fn default_include_path(sdk_path: &Path) -> BoxResult<PathBuf> {
 
     Ok(include_path)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(build: &str) -> Build {
+        Build::parse(build).unwrap()
+    }
+
+    #[test]
+    fn parse_builds_and_archive_names() {
+        let expected = Build {
+            major: 1,
+            minor: 12,
+            patch: 0,
+            build: 7210,
+        };
+        assert_eq!(build("1.12.0-git7210"), expected);
+        assert_eq!(build("sourcemod-1.12.0-git7210-linux.tar.gz"), expected);
+        assert_eq!(
+            expected.archive(&Runtime::Metamod),
+            "mmsource-1.12.0-git7210-linux.tar.gz"
+        );
+        for invalid in ["1.12.0", "1.12-git7210", "1.12.0-gitx", ""] {
+            assert!(Build::parse(invalid).is_err(), "{} was accepted", invalid);
+        }
+    }
+
+    #[test]
+    fn parse_requirements() {
+        let pin = BuildRequirement::parse("1.12.0-git7210").unwrap();
+        assert_eq!(pin.exact(), Some(&build("1.12.0-git7210")));
+        assert_eq!(pin.to_string(), "=1.12.0-git7210");
+
+        let range = BuildRequirement::parse(">=1.12.0-git7100, <1.12.0-git7300").unwrap();
+        assert_eq!(range.exact(), None);
+        assert!(range.matches(&build("1.12.0-git7100")));
+        assert!(range.matches(&build("1.12.0-git7299")));
+        assert!(!range.matches(&build("1.12.0-git7300")));
+        assert!(!range.matches(&build("1.11.0-git6968")));
+
+        assert!(BuildRequirement::parse(">=1.12").is_err());
+        assert!(BuildRequirement::parse("1.12.0-git7100,").is_err());
+    }
+
+    #[test]
+    fn newest_matching_build_is_picked() {
+        let listing = r#"
+            <a href="sourcemod-1.12.0-git7195-linux.tar.gz">sourcemod-1.12.0-git7195-linux.tar.gz</a>
+            <a href="sourcemod-1.12.0-git7195-windows.zip">sourcemod-1.12.0-git7195-windows.zip</a>
+            <a href="sourcemod-1.12.0-git7210-linux.tar.gz">sourcemod-1.12.0-git7210-linux.tar.gz</a>
+            <a href="sourcemod-1.12.0-git7202-linux.tar.gz">sourcemod-1.12.0-git7202-linux.tar.gz</a>
+            <a href="mmsource-1.12.0-git1219-linux.tar.gz">mmsource-1.12.0-git1219-linux.tar.gz</a>
+        "#;
+        let builds = parse_listing(&Runtime::Sourcemod, listing);
+        assert_eq!(
+            builds,
+            vec![
+                build("1.12.0-git7195"),
+                build("1.12.0-git7202"),
+                build("1.12.0-git7210")
+            ]
+        );
+
+        let below = BuildRequirement::parse("<1.12.0-git7210").unwrap();
+        assert_eq!(below.newest(&builds), Some(&build("1.12.0-git7202")));
+        let above = BuildRequirement::parse(">1.12.0-git7210").unwrap();
+        assert_eq!(above.newest(&builds), None);
+    }
+
+    #[test]
+    fn branches_map_to_drop_directories() {
+        let manager = Manager::new(Path::new("/"));
+        let version = |runtime, branch| manager.get_sdk_branch_version(&runtime, &branch);
+        assert_eq!(version(Runtime::Sourcemod, Branch::Stable), "1.12");
+        assert_eq!(version(Runtime::Sourcemod, Branch::Dev), "1.13");
+        assert_eq!(version(Runtime::Metamod, Branch::Stable), "1.12");
+        assert_eq!(version(Runtime::Metamod, Branch::Dev), "2.0");
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{BoxResult, STATE_DIR, fsutil};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// A set of files prepared under .sm-pkg/staging, mirroring the project root, and then swapped
+/// into the project with renames. Files that get replaced or removed are moved into
+/// .sm-pkg/backup first so everything can be put back if any step fails.
+pub struct Stage {
+    project_root: PathBuf,
+    root: PathBuf,
+    backup: PathBuf,
+}
+
+enum Step {
+    /// A staged file was moved into place, replacing an existing file when `backed_up` is set.
+    Placed { path: PathBuf, backed_up: bool },
+    /// A file was removed from the project.
+    Removed { path: PathBuf },
+}
+
+impl Stage {
+    /// Start a new, empty stage for `project_root`. Anything left over from a previous attempt
+    /// that never got as far as swapping files in is discarded.
+    pub fn new(project_root: &Path) -> BoxResult<Self> {
+        let state_dir = project_root.join(STATE_DIR);
+        let stage = Stage {
+            project_root: project_root.to_path_buf(),
+            root: state_dir.join("staging"),
+            backup: state_dir.join("backup"),
+        };
+        if stage.backup.exists() {
+            return Err(format!(
+                "❗ {} exists, a previous install was interrupted while swapping files in. \
+                 Restore the files in it or remove it before trying again",
+                stage.backup.display()
+            )
+            .into());
+        }
+        if stage.root.exists() {
+            fs::remove_dir_all(&stage.root)?;
+        }
+        fs::create_dir_all(&stage.root)?;
+        Ok(stage)
+    }
+
+    /// Directory mirroring the project root that files are prepared in.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Swap every staged file into the project and remove `remove`, paths relative to the
+    /// project root. If anything fails the project is restored to how it was before.
+    pub fn commit(self, remove: &[PathBuf]) -> BoxResult {
+        let mut steps = Vec::new();
+        match self.apply(remove, &mut steps) {
+            Ok(()) => {
+                fs::remove_dir_all(&self.root)?;
+                if self.backup.exists() {
+                    fs::remove_dir_all(&self.backup)?;
+                }
+                Ok(())
+            }
+            Err(e) => {
+                error!(
+                    "Failed to swap in staged files, restoring previous ones: {}",
+                    e
+                );
+                self.rollback(&steps)?;
+                Err(e)
+            }
+        }
+    }
+
+    fn apply(&self, remove: &[PathBuf], steps: &mut Vec<Step>) -> BoxResult {
+        for path in fsutil::list_files(&self.root)? {
+            let live = self.project_root.join(&path);
+            let backed_up = live.exists();
+            if backed_up {
+                move_file(&live, &self.backup.join(&path))?;
+            }
+            steps.push(Step::Placed {
+                path: path.clone(),
+                backed_up,
+            });
+            move_file(&self.root.join(&path), &live)?;
+        }
+        for path in remove {
+            let live = self.project_root.join(path);
+            if !live.exists() {
+                continue;
+            }
+            move_file(&live, &self.backup.join(path))?;
+            steps.push(Step::Removed { path: path.clone() });
+        }
+        Ok(())
+    }
+
+    fn rollback(&self, steps: &[Step]) -> BoxResult {
+        for step in steps.iter().rev() {
+            match step {
+                Step::Placed { path, backed_up } => {
+                    let live = self.project_root.join(path);
+                    if live.exists() {
+                        fs::remove_file(&live)?;
+                    }
+                    if *backed_up {
+                        move_file(&self.backup.join(path), &live)?;
+                    }
+                }
+                Step::Removed { path } => {
+                    move_file(&self.backup.join(path), &self.project_root.join(path))?;
+                }
+            }
+        }
+        for dir in [&self.backup, &self.root] {
+            if dir.exists() {
+                fs::remove_dir_all(dir)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(from, to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::write;
+
+    #[test]
+    fn commit_swaps_files_in_and_removes_stale_ones() {
+        let root = tempfile::tempdir().unwrap();
+        write(&root.path().join("cfg/server.cfg"), "old");
+        write(&root.path().join("plugins/stale.smx"), "stale");
+
+        let stage = Stage::new(root.path()).unwrap();
+        write(&stage.root().join("cfg/server.cfg"), "new");
+        write(&stage.root().join("plugins/rip.smx"), "rip");
+        stage.commit(&[PathBuf::from("plugins/stale.smx")]).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(root.path().join("cfg/server.cfg")).unwrap(),
+            "new"
+        );
+        assert_eq!(
+            fs::read_to_string(root.path().join("plugins/rip.smx")).unwrap(),
+            "rip"
+        );
+        assert!(!root.path().join("plugins/stale.smx").exists());
+        assert!(!root.path().join(STATE_DIR).join("backup").exists());
+        assert!(!root.path().join(STATE_DIR).join("staging").exists());
+    }
+
+    #[test]
+    fn failed_commit_restores_the_previous_tree() {
+        let root = tempfile::tempdir().unwrap();
+        write(&root.path().join("a/replaced.cfg"), "original");
+        // A file where the stage needs a directory makes the swap fail after `a` is placed.
+        write(&root.path().join("b"), "in the way");
+        write(&root.path().join("c/removed.smx"), "kept");
+
+        let stage = Stage::new(root.path()).unwrap();
+        write(&stage.root().join("a/added.smx"), "added");
+        write(&stage.root().join("a/replaced.cfg"), "replacement");
+        write(&stage.root().join("b/blocked.smx"), "blocked");
+        assert!(stage.commit(&[PathBuf::from("c/removed.smx")]).is_err());
+
+        let files = fsutil::list_files(root.path()).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from("a/replaced.cfg"),
+                PathBuf::from("b"),
+                PathBuf::from("c/removed.smx"),
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(root.path().join("a/replaced.cfg")).unwrap(),
+            "original"
+        );
+        assert_eq!(
+            fs::read_to_string(root.path().join("b")).unwrap(),
+            "in the way"
+        );
+        // A fresh stage can be started, nothing is left in backup.
+        assert!(Stage::new(root.path()).is_ok());
+    }
+}
@@ -1,4 +1,6 @@
+use schemars::{JsonSchema, Schema, SchemaGenerator};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 
 /// Enum representing an integer boolean value. This functionality probably exists somewhere else, idk.
 #[derive(Debug)]
@@ -32,6 +34,21 @@ impl<'de> Deserialize<'de> for IntBool {
     }
 }
 
+// Written as a plain yaml boolean, see the Deserialize impl above.
+impl JsonSchema for IntBool {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        bool::schema_name()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        bool::json_schema(generator)
+    }
+}
+
 impl std::fmt::Display for IntBool {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -2,24 +2,30 @@ mod intbool;
 mod onoffbool;
 mod yesnobool;
 
+use crate::{BoxResult, games::GameInfo};
 use askama::Template;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 
 use intbool::*;
 use onoffbool::*;
 use yesnobool::*;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub enum Format {
     CFG,
     KV,
     TEMPLATE,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub enum ReserveType {
+    /// Public slots are used in preference to reserved slots. Reserved slots are freed before public slots.
     Public = 0,
+    /// If someone with reserve access joins into a reserved slot, the player with the highest latency and no reserved slot access (spectator players are selected first) is kicked to make room.
     DropHighLatency = 1,
+    /// The same as DropHighLatency, except the maximum number of reserved slots is limited by sm_reserved_slots.
     DropHighLatencyLimited = 2,
 }
 
@@ -33,11 +39,15 @@ impl std::fmt::Display for ReserveType {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub enum ImmunityMode {
+    /// Ignore immunity levels (except for specific group immunities).
     Ignore = 0,
+    /// Protect from admins of lower access only.
     ProtectLowerAccessOnly = 1,
+    /// Protect from admins of equal to or lower access.
     ProtectEqualOrLowerAccess = 2,
+    /// Same as ProtectEqualOrLowerAccess, except admins with no immunity can affect each other.
     ProtectEqualOrLowerAccessNoAdminImmunity = 3,
 }
 
@@ -52,189 +62,317 @@ impl std::fmt::Display for ImmunityMode {
     }
 }
 
-#[derive(Template, Serialize, Deserialize, Debug)]
+#[derive(Template, Serialize, Deserialize, JsonSchema, Debug)]
 #[template(
     path = "cfg/sourcemod/sourcemod.cfg.jinja2",
     ext = "txt",
     whitespace = "suppress"
 )]
+#[serde(deny_unknown_fields)]
 pub struct SourcemodCfg {
+    /// Specifies how admin activity should be relayed to users. Add up the values of the options you want: 1 show to non-admins, 2 show admin names to non-admins, 4 show to admins, 8 show admin names to admins, 16 always show admin names to root users.
+    #[schemars(range(max = 31))]
     pub sm_show_activity: Option<u8>,
+    /// Specifies whether menu sounds are enabled for menus created by SourceMod.
     pub sm_menu_sounds: Option<IntBool>,
+    /// Specifies how long of a delay, in seconds, should be used in between votes that are "public" or can be spammed.
     pub sm_vote_delay: Option<u32>,
+    /// Default datetime formatting rules when displaying to clients. For full options, see: http://www.opengroup.org/onlinepubs/007908799/xsh/strftime.html
     pub sm_datetime_format: Option<String>,
+    /// Sets how SourceMod should check immunity levels when administrators target each other.
     pub sm_immunity_mode: Option<ImmunityMode>,
+    /// Sets how many seconds SourceMod should adjust time values for incorrect server clocks.
     pub sm_time_adjustment: Option<u32>,
+    /// Specifies the amount of time that is allowed between chat messages.
+    #[schemars(range(min = 0.0))]
     pub sm_flood_time: Option<f32>,
+    /// Specifies how the reserved slots plugin operates.
     pub sm_reserve_type: Option<ReserveType>,
+    /// Specifies the number of reserved player slots.
     pub sm_reserved_slots: Option<u8>,
+    /// Specifies whether or not reserved slots will be hidden (subtracted from max slot count).
     pub sm_hide_slots: Option<IntBool>,
+    /// Specifies whether or not non-admins can send messages to admins using say_team @<message>.
     pub sm_chat_mode: Option<IntBool>,
+    /// Specifies whether or not "timeleft" will automatically be triggered every x seconds. Valid values are 0 (Disabled) to 1800 seconds.
+    #[schemars(range(max = 1800))]
     pub sm_timeleft_interval: Option<u32>,
+    /// Specifies whether or not chat triggers are broadcast to the server or just the player who requested the info trigger.
     pub sm_trigger_show: Option<IntBool>,
+    /// Specifies whether or not to display vote progress to clients in the "hint" box (near the bottom of the screen in most games).
     pub sm_vote_progress_hintbox: Option<IntBool>,
+    /// Specifies whether or not to display vote progress to clients in the chat area.
     pub sm_vote_progress_chat: Option<IntBool>,
+    /// Specifies whether or not to display vote progress in the server console.
     pub sm_vote_progress_console: Option<IntBool>,
+    /// Specifies whether or not to display vote progress to clients in the client console.
     pub sm_vote_progress_client_console: Option<IntBool>,
 }
 
-#[derive(Template, Serialize, Deserialize, Debug)]
+#[derive(Template, Serialize, Deserialize, JsonSchema, Debug)]
 #[template(
     path = "addons/sourcemod/configs/core.cfg.jinja2",
     ext = "txt",
     whitespace = "suppress"
 )]
+#[serde(deny_unknown_fields)]
 pub struct CoreCfg {
+    /// This option determines if SourceMod logging is enabled.
     pub logging: Option<OnOffBool>,
+    /// This option determines how SourceMod logging should be handled: daily, map or game.
     pub log_mode: Option<String>,
+    /// This option determines the time format SourceMod logging should use.
     pub log_time_format: Option<String>,
+    /// Language that multilingual enabled plugins and extensions will use to print messages. Only languages listed in languages.cfg are valid.
     pub server_lang: Option<String>,
+    /// List of characters to use for public chat triggers. Set an empty list to disable.
     pub public_chat_trigger: Option<String>,
+    /// List of characters to use for silent chat triggers. Set an empty list to disable.
     pub silent_chat_trigger: Option<String>,
+    /// If a say command is a silent chat trigger, and is used by an admin, but it does not evaluate to an actual command, it will be displayed publicly. This setting allows you to suppress accidental typings.
     pub silent_fail_suppress: Option<YesNoBool>,
+    /// Password setinfo key that clients must set. You must change this in order for passwords to work, for security reasons.
     pub pass_info_var: Option<String>,
+    /// Enables or disables whether SourceMod reads a client's cl_language cvar to set their language for server-side phrase translation.
     pub allow_cl_language_var: Option<OnOffBool>,
+    /// Enables or Disables SourceMod's automatic gamedata updating.
     pub disable_auto_update: Option<YesNoBool>,
+    /// If set, a successful gamedata update will attempt to restart SourceMod. SourceMod is unloaded and reloaded, and the map is changed to the current map.
     pub force_restart_after_update: Option<YesNoBool>,
+    /// URL to use for retrieving update information. SSL is not yet supported.
     pub auto_update_url: Option<String>,
+    /// Whether to show debug spew. Currently this will log details about the gamedata updating process.
     pub debug_spew: Option<YesNoBool>,
+    /// If set, SourceMod will validate steamid auth strings with the Steam backend before giving out admin access.
     pub steam_authstring_validation: Option<YesNoBool>,
+    /// Enables or disables whether SourceMod blocks known or potentially malicious plugins from loading. It is STRONGLY advised that this is left enabled.
     pub block_bad_plugins: Option<YesNoBool>,
+    /// Seconds a plugin may hang the server before SourceMod attempts to terminate it. 0 disables the check.
     pub slow_script_timeout: Option<u32>,
+    /// Block plugins from using functionality known to get Game Server Login Tokens banned. Only has an effect on CS:GO.
     pub follow_csgo_server_guidelines: Option<YesNoBool>,
+    /// Controls whether the SourcePawn runtime will generate additional metadata about JIT-compiled functions for performance profiling or debugging purposes.
     pub jit_metadata: Option<String>,
+    /// Setup the SourcePawn VM to enable extensions to use a debugging API to step through plugins line by line. This heavily decreases server performance and should NEVER be used on a production server.
     pub enable_line_debugging: Option<YesNoBool>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct SourcemodDatabase {
+    /// Name of the database section, used by plugins to look it up.
     pub name: String,
+    /// Database name, or file name for sqlite.
     pub database: String,
+    /// Database driver to use, eg: mysql or sqlite.
     pub driver: Option<String>,
+    /// Host of the database.
     pub host: Option<String>,
+    /// Port of the database.
     pub port: Option<u16>,
+    /// Username of the database.
     pub user: Option<String>,
+    /// Password of the database.
     pub pass: Option<String>,
+    /// Connection timeout in seconds.
     pub timeout: Option<u32>,
 }
 
-#[derive(Template, Serialize, Deserialize)]
+#[derive(Template, Serialize, Deserialize, JsonSchema)]
 #[template(
     path = "addons/sourcemod/configs/databases.cfg.jinja2",
     ext = "txt",
     whitespace = "suppress"
 )]
+#[serde(deny_unknown_fields)]
 pub struct DatabasesCfg {
+    /// Default database driver to use.
     pub driver_default: Option<String>,
+    /// Database connections available to plugins.
     pub databases: Option<Vec<SourcemodDatabase>>,
 }
 
-#[derive(Template, Serialize, Deserialize)]
+#[derive(Template, Serialize, Deserialize, JsonSchema)]
 #[template(
     path = "addons/sourcemod/configs/maplists.cfg.jinja2",
     ext = "txt",
     whitespace = "suppress"
 )]
+#[serde(deny_unknown_fields)]
 pub struct MaplistsCfg {
+    /// Target of the default section, "mapcyclefile" maps to the file named by the mapcyclefile cvar.
     pub default_target: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct SourcemodSimpleAdmin {
+    /// For the Identity, you can use a SteamID or Name. To use an IP address, prepend a ! character.
     pub identity: String,
+    /// Passwords are generally not needed unless you have name-based authentication.
     pub password: Option<String>,
+    /// Admin flags of the user.
     pub flags: String,
+    /// An admin cannot target an admin with a higher immunity value (see sm_immunity_mode to tweak the rules). Default immunity value is 0 (no immunity).
     pub immunity: Option<u8>,
 }
 
-#[derive(Template, Serialize, Deserialize)]
+#[derive(Template, Serialize, Deserialize, JsonSchema)]
 #[template(
     path = "addons/sourcemod/configs/admins_simple.ini.jinja2",
     ext = "txt",
     whitespace = "suppress"
 )]
+#[serde(deny_unknown_fields)]
 pub struct AdminsSimpleIni {
+    /// Admins to write to admins_simple.ini.
     pub users: Option<Vec<SourcemodSimpleAdmin>>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct SourcemodAdmin {
+    /// The name of the user.
     pub name: String,
+    /// Auth method to use, eg: steam, name or ip.
     pub auth: String,
+    /// Identification string, for example, a steamid or name.
     pub identity: String,
+    /// Optional password to require.
     pub password: Option<String>,
+    /// Adds one group to the user's group table.
     pub group: Option<String>,
+    /// Adds one or more flags to the user's permissions.
     pub flags: Option<String>,
+    /// An admin cannot target an admin with a higher immunity value (see sm_immunity_mode to tweak the rules). Default immunity value is 0 (no immunity).
     pub immunity: Option<u8>,
 }
 
-#[derive(Template, Serialize, Deserialize)]
+#[derive(Template, Serialize, Deserialize, JsonSchema)]
 #[template(
     path = "addons/sourcemod/configs/admins.cfg.jinja2",
     ext = "txt",
     whitespace = "suppress"
 )]
+#[serde(deny_unknown_fields)]
 pub struct AdminsCfg {
+    /// Admins to write to admins.cfg.
     pub users: Option<Vec<SourcemodAdmin>>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct Override {
+    /// The name of the command being overridden.
     pub command: String,
+    /// Either allow or deny.
     pub action: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct AdminGroup {
+    /// The name of the group.
     pub name: String,
+    /// Permission flags granted to members of the group.
     pub flags: Option<String>,
+    /// Immunity value given to members of the group.
     pub immunity: Option<u8>,
+    /// Command overrides that apply to members of the group.
     pub overrides: Option<Vec<Override>>,
 }
 
-#[derive(Template, Serialize, Deserialize)]
+#[derive(Template, Serialize, Deserialize, JsonSchema)]
 #[template(
     path = "addons/sourcemod/configs/admin_groups.cfg.jinja2",
     ext = "txt",
     whitespace = "suppress"
 )]
+#[serde(deny_unknown_fields)]
 pub struct AdminGroupsCfg {
+    /// Immunity given to groups that do not set one.
     pub default_immunity: Option<String>,
+    /// Admin groups to write to admin_groups.cfg.
     pub groups: Option<Vec<AdminGroup>>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct AdminOverride {
+    /// Command to override, prefix with @ to override a command group.
     pub command: String,
+    /// Flags required to use the command, empty to allow everyone.
     pub flags: String,
 }
 
-#[derive(Template, Serialize, Deserialize)]
+#[derive(Template, Serialize, Deserialize, JsonSchema)]
 #[template(
     path = "addons/sourcemod/configs/admin_overrides.cfg.jinja2",
     ext = "txt",
     whitespace = "suppress"
 )]
+#[serde(deny_unknown_fields)]
 pub struct AdminOverridesCfg {
+    /// Overrides to write to admin_overrides.cfg.
     pub overrides: Option<Vec<AdminOverride>>,
 }
 
-#[derive(Template, Serialize, Deserialize)]
+#[derive(Template, Serialize, Deserialize, JsonSchema, Clone)]
 #[template(path = "start.sh.jinja2", ext = "txt")]
+#[serde(deny_unknown_fields)]
 pub struct StartSh {
+    /// Run srcds_run_64 instead of srcds_run. Defaults to 64-bit when the game provides it.
     pub use_64bit: Option<bool>,
-    pub mod_folder: String,
+    /// Folder containing the game server mod. Defaults to the mod folder of the game.
+    pub mod_folder: Option<String>,
+    /// Port to run the game server on.
+    #[schemars(range(min = 1))]
     pub port: Option<u16>,
+    /// Port to run SourceTV on.
+    #[schemars(range(min = 1))]
     pub tv_port: Option<u16>,
+    /// Client port.
+    #[schemars(range(min = 1))]
     pub client_port: Option<u16>,
+    /// Maximum number of players allowed.
     pub max_players: Option<u8>,
+    /// Unrestrict max players.
     pub unrestricted_max_players: Option<bool>,
+    /// Startup map. Defaults to a stock map of the game.
     pub map: Option<String>,
+    /// Game server login token. see: https://steamcommunity.com/dev/managegameservers
     pub gslt: Option<String>,
+    /// RCON password.
     pub rcon_password: Option<String>,
+    /// Server password.
     pub sv_password: Option<String>,
+    /// Region to use. see: https://developer.valvesoftware.com/wiki/Sv_region
     pub region: Option<u8>,
+    /// IP to listen on.
     pub ip: Option<String>,
+    /// Enable the Steam Datagram Relay network.
     pub sdr_enable: Option<bool>,
+    /// Steam web API key used to download workshop maps.
     pub workshop_authkey: Option<String>,
 }
+
+impl StartSh {
+    /// Fill in any unset options with the defaults for the game being run.
+    pub fn with_game_defaults(&self, game: &GameInfo, mod_folder: &Path) -> BoxResult<StartSh> {
+        let mut opts = self.clone();
+        match opts.use_64bit {
+            Some(true) if !game.has_64bit => {
+                return Err(format!("{} does not provide a 64-bit server", game.name).into());
+            }
+            None => opts.use_64bit = Some(game.has_64bit),
+            _ => (),
+        }
+        if opts.mod_folder.is_none() {
+            opts.mod_folder = Some(mod_folder.display().to_string());
+        }
+        if opts.map.is_none() {
+            opts.map = Some(game.default_map.to_string());
+        }
+        Ok(opts)
+    }
+}
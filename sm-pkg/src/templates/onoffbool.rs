@@ -1,4 +1,6 @@
+use schemars::{JsonSchema, Schema, SchemaGenerator};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 
 /// Enum representing a string boolean value with On/Off as values. This functionality probably exists somewhere else, idk.
 #[derive(Debug)]
@@ -13,8 +15,8 @@ impl Serialize for OnOffBool {
         S: serde::Serializer,
     {
         match self {
-            OnOffBool::False => serializer.serialize_str("off"),
-            OnOffBool::True => serializer.serialize_str("on"),
+            OnOffBool::False => serializer.serialize_bool(false),
+            OnOffBool::True => serializer.serialize_bool(true),
         }
     }
 }
@@ -32,6 +34,21 @@ impl<'de> Deserialize<'de> for OnOffBool {
     }
 }
 
+// Written as a plain yaml boolean, see the Deserialize impl above.
+impl JsonSchema for OnOffBool {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        bool::schema_name()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        bool::json_schema(generator)
+    }
+}
+
 impl std::fmt::Display for OnOffBool {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
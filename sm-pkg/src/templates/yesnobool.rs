@@ -1,4 +1,6 @@
+use schemars::{JsonSchema, Schema, SchemaGenerator};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 
 /// Enum representing a string boolean value with On/Off as values. This functionality probably exists somewhere else, idk.
 #[derive(Debug)]
@@ -32,6 +34,21 @@ impl<'de> Deserialize<'de> for YesNoBool {
     }
 }
 
+// Written as a plain yaml boolean, see the Deserialize impl above.
+impl JsonSchema for YesNoBool {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        bool::schema_name()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        bool::json_schema(generator)
+    }
+}
+
 impl std::fmt::Display for YesNoBool {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
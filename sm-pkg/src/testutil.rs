@@ -0,0 +1,68 @@
+//! Fixtures shared by the unit tests.
+
+use crate::{BoxResult, plugins::Definition, repo::PluginDefinitionProvider};
+use std::{fs, path::Path};
+
+/// In memory plugin index.
+pub struct TestRepo {
+    pub plugins: Vec<Definition>,
+}
+
+impl TestRepo {
+    /// Index of `(name, version, dependencies)` plugins.
+    pub fn new(plugins: &[(&str, &str, &[&str])]) -> Self {
+        let plugins = plugins
+            .iter()
+            .map(|(name, version, dependencies)| {
+                let mut definition = definition(name, version);
+                definition.dependencies =
+                    Some(dependencies.iter().map(|d| d.to_string()).collect());
+                definition
+            })
+            .collect();
+        TestRepo { plugins }
+    }
+}
+
+impl<'p> PluginDefinitionProvider<'p> for TestRepo {
+    fn find_plugin_definitions(&self, plugins: &[String]) -> BoxResult<Vec<Definition>> {
+        plugins
+            .iter()
+            .map(|plugin| self.find_plugin_definition(plugin))
+            .collect()
+    }
+
+    fn find_plugin_definition(&self, plugin: &str) -> BoxResult<Definition> {
+        match self.plugins.iter().find(|d| d.name == plugin) {
+            Some(definition) => Ok(definition.clone()),
+            None => Err(format!("Plugin not found: {}", plugin).into()),
+        }
+    }
+
+    fn provides(&self, plugin: &str) -> bool {
+        self.plugins.iter().any(|d| d.name == plugin)
+    }
+
+    fn plugins(&self) -> BoxResult<Vec<Definition>> {
+        Ok(self.plugins.clone())
+    }
+
+    fn plugin_revision(&self, _plugin: &str) -> Option<String> {
+        None
+    }
+}
+
+/// A plugin definition with only the required fields set.
+pub fn definition(name: &str, version: &str) -> Definition {
+    serde_yaml::from_str(&format!(
+        "name: {}\ndescription: {} plugin\nversion: {}",
+        name, name, version
+    ))
+    .unwrap()
+}
+
+/// Write `contents` to `path`, creating missing parent directories.
+pub fn write(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
@@ -0,0 +1,333 @@
+use crate::{
+    BoxResult, fsutil,
+    lock::Lockfile,
+    manifest::Manifest,
+    sdk::{self, Runtime},
+};
+use serde::Serialize;
+use similar::{Algorithm, DiffOp, capture_diff_slices};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+/// What happened to a stock config file shipped with sourcemod or metamod during an install.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigUpgrade {
+    /// The local copy was unmodified and has been replaced by the new stock version.
+    Replaced,
+    /// The local copy has edits that were kept, the stock version did not change.
+    Kept,
+    /// Upstream changes were merged into the locally edited copy.
+    Merged,
+    /// The local copy was kept as is because the upstream changes could not be merged, or there
+    /// was no previous stock version to merge against.
+    Skipped,
+}
+
+impl Display for ConfigUpgrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigUpgrade::Replaced => write!(f, "replaced"),
+            ConfigUpgrade::Kept => write!(f, "kept"),
+            ConfigUpgrade::Merged => write!(f, "merged"),
+            ConfigUpgrade::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+/// Whether a file from a sourcemod or metamod archive, relative to the mod folder, is a config
+/// file users are expected to edit rather than part of the runtime itself.
+pub fn is_config(path: &Path) -> bool {
+    path.starts_with("addons/sourcemod/configs")
+        || path.starts_with("cfg")
+        || path
+            .extension()
+            .is_some_and(|ext| ext == "cfg" || ext == "ini")
+}
+
+/// Stock config files of the sourcemod and metamod builds recorded in `lock`, keyed by their
+/// path relative to the project root. Used as the merge base by [`reconcile_configs`]. Builds
+/// that cannot be fetched are left out, which only means edited files are kept as they are.
+pub async fn previous_stock_configs(
+    manager: &sdk::Manager<'_>,
+    lock: Option<&Lockfile>,
+    mod_folder: &Path,
+) -> HashMap<PathBuf, Vec<u8>> {
+    let mut configs = HashMap::new();
+    let Some(lock) = lock else {
+        return configs;
+    };
+    let installed = [
+        (Runtime::Metamod, &lock.metamod),
+        (Runtime::Sourcemod, &lock.sourcemod),
+    ];
+    for (runtime, archive) in installed {
+        let Some(archive) = archive else {
+            continue;
+        };
+        let files = match manager.cached_archive(&runtime, archive).await {
+            Ok(path) => manager.read_archive(&path, is_config),
+            Err(e) => Err(e),
+        };
+        match files {
+            Ok(files) => configs.extend(
+                files
+                    .into_iter()
+                    .map(|(path, contents)| (mod_folder.join(path), contents)),
+            ),
+            Err(e) => warn!("Cannot read the stock configs of {}: {}", archive, e),
+        }
+    }
+    configs
+}
+
+/// Reconcile the stock config files of freshly staged runtime archives with the live game
+/// directory, so upgrading never throws away local edits.
+///
+/// `files` are the staged config files relative to the project root and `base` holds the stock
+/// contents of the previously installed version, keyed the same way. Live files that were left
+/// as installed, according to `previous`, are replaced. Edited ones are three-way merged with the
+/// new stock version when possible and otherwise kept untouched. Files in `skip`, generated ones,
+/// are left alone.
+pub fn reconcile_configs(
+    project_root: &Path,
+    stage_root: &Path,
+    files: &[PathBuf],
+    previous: &Manifest,
+    base: &HashMap<PathBuf, Vec<u8>>,
+    skip: &[PathBuf],
+) -> BoxResult<Vec<(PathBuf, ConfigUpgrade)>> {
+    let mut results = Vec::new();
+    for path in files {
+        let live = project_root.join(path);
+        let staged = stage_root.join(path);
+        if skip.contains(path) || !live.exists() || !staged.exists() {
+            continue;
+        }
+        let ours = fs::read(&live)?;
+        let theirs = fs::read(&staged)?;
+        if ours == theirs {
+            continue;
+        }
+        if previous
+            .find(path)
+            .is_some_and(|f| fsutil::hash_file(&live).is_ok_and(|hash| hash == f.stock_hash()))
+        {
+            results.push((path.clone(), ConfigUpgrade::Replaced));
+            continue;
+        }
+        let outcome = match base.get(path) {
+            Some(base) if *base == theirs => ConfigUpgrade::Kept,
+            Some(base) => match merge3(base, &ours, &theirs) {
+                Some(merged) => {
+                    fs::write(&staged, merged)?;
+                    ConfigUpgrade::Merged
+                }
+                None => ConfigUpgrade::Skipped,
+            },
+            None => ConfigUpgrade::Skipped,
+        };
+        if outcome != ConfigUpgrade::Merged {
+            fs::copy(&live, &staged)?;
+        }
+        results.push((path.clone(), outcome));
+    }
+    Ok(results)
+}
+
+/// Line based three-way merge of `ours` and `theirs`, both derived from `base`. Returns None
+/// when both sides changed the same, or adjacent, lines differently.
+pub fn merge3(base: &[u8], ours: &[u8], theirs: &[u8]) -> Option<Vec<u8>> {
+    let base = split_lines(base);
+    let ours = split_lines(ours);
+    let theirs = split_lines(theirs);
+    let mut all = changes(&base, &ours);
+    all.extend(changes(&base, &theirs));
+    all.sort_by_key(|(range, _)| (range.start, range.end));
+
+    let mut merged: Vec<u8> = Vec::new();
+    let mut position = 0;
+    let mut last: Option<&Change> = None;
+    for change in &all {
+        let (range, lines) = change;
+        if let Some((last_range, last_lines)) = last
+            && range.start <= last_range.end
+            && last_range.start <= range.end
+        {
+            if last_range == range && last_lines == lines {
+                continue;
+            }
+            return None;
+        }
+        for line in &base[position..range.start] {
+            merged.extend_from_slice(line);
+        }
+        for line in lines {
+            merged.extend_from_slice(line);
+        }
+        position = range.end;
+        last = Some(change);
+    }
+    for line in &base[position..] {
+        merged.extend_from_slice(line);
+    }
+    Some(merged)
+}
+
+/// A changed region of the base lines and the lines replacing it.
+type Change<'a> = (Range<usize>, Vec<&'a [u8]>);
+
+/// Changes turning `base` into `other`. Neighbouring edits are joined into one change so that
+/// only edits from different sides can ever touch.
+fn changes<'a>(base: &[&'a [u8]], other: &[&'a [u8]]) -> Vec<Change<'a>> {
+    let mut changes: Vec<Change> = Vec::new();
+    for op in capture_diff_slices(Algorithm::Myers, base, other) {
+        if let DiffOp::Equal { .. } = op {
+            continue;
+        }
+        let (range, lines) = (op.old_range(), &other[op.new_range()]);
+        match changes.last_mut() {
+            Some((last, last_lines)) if last.end >= range.start => {
+                last.end = range.end;
+                last_lines.extend_from_slice(lines);
+            }
+            _ => changes.push((range, lines.to_vec())),
+        }
+    }
+    changes
+}
+
+fn split_lines(data: &[u8]) -> Vec<&[u8]> {
+    data.split_inclusive(|b| *b == b'\n').collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::write;
+
+    const STOCK: &str = "sm_flood_time 0.75\nsm_reserve_type 0\nsm_vote_delay 30\n";
+
+    /// Reconcile sourcemod.cfg installed from `STOCK` and edited into `live`, against a new
+    /// stock version `theirs`. Returns the outcome and the file that ends up staged.
+    fn reconcile(live: &str, theirs: &str) -> (Vec<(PathBuf, ConfigUpgrade)>, String) {
+        let project = tempfile::tempdir().unwrap();
+        let stage = tempfile::tempdir().unwrap();
+        let path = PathBuf::from("cstrike/cfg/sourcemod/sourcemod.cfg");
+        write(&project.path().join(&path), STOCK);
+        write(&stage.path().join(&path), theirs);
+        let mut previous = Manifest::default();
+        previous
+            .record(project.path(), "sourcemod", &project.path().join(&path))
+            .unwrap();
+        fs::write(project.path().join(&path), live).unwrap();
+        let base = HashMap::from([(path.clone(), STOCK.as_bytes().to_vec())]);
+
+        let results = reconcile_configs(
+            project.path(),
+            stage.path(),
+            std::slice::from_ref(&path),
+            &previous,
+            &base,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(project.path().join(&path)).unwrap(),
+            live,
+            "the live file is never written to"
+        );
+        let staged = fs::read_to_string(stage.path().join(&path)).unwrap();
+        (results, staged)
+    }
+
+    #[test]
+    fn untouched_stock_file_is_updated() {
+        let theirs = "sm_flood_time 0.75\nsm_reserve_type 1\nsm_vote_delay 30\n";
+        let (results, staged) = reconcile(STOCK, theirs);
+        assert_eq!(results[0].1, ConfigUpgrade::Replaced);
+        assert_eq!(staged, theirs);
+    }
+
+    #[test]
+    fn local_edit_is_kept_when_stock_is_unchanged() {
+        let live = "sm_flood_time 2\nsm_reserve_type 0\nsm_vote_delay 30\n";
+        let (results, staged) = reconcile(live, STOCK);
+        assert_eq!(results[0].1, ConfigUpgrade::Kept);
+        assert_eq!(staged, live);
+    }
+
+    #[test]
+    fn local_edit_is_merged_with_upstream_changes() {
+        let live = "sm_flood_time 2\nsm_reserve_type 0\nsm_vote_delay 30\n";
+        let theirs = "sm_flood_time 0.75\nsm_reserve_type 0\nsm_vote_delay 60\n";
+        let (results, staged) = reconcile(live, theirs);
+        assert_eq!(results[0].1, ConfigUpgrade::Merged);
+        assert_eq!(
+            staged,
+            "sm_flood_time 2\nsm_reserve_type 0\nsm_vote_delay 60\n"
+        );
+    }
+
+    #[test]
+    fn conflicting_edit_is_skipped_without_overwriting() {
+        let live = "sm_flood_time 2\nsm_reserve_type 0\nsm_vote_delay 30\n";
+        let theirs = "sm_flood_time 1\nsm_reserve_type 0\nsm_vote_delay 30\n";
+        let (results, staged) = reconcile(live, theirs);
+        assert_eq!(results[0].1, ConfigUpgrade::Skipped);
+        assert_eq!(staged, live);
+    }
+
+    #[test]
+    fn kept_edits_recorded_in_the_manifest_stay_kept() {
+        let project = tempfile::tempdir().unwrap();
+        let stage = tempfile::tempdir().unwrap();
+        let path = PathBuf::from("cstrike/cfg/sourcemod/sourcemod.cfg");
+        let live = "sm_flood_time 2\nsm_reserve_type 0\nsm_vote_delay 30\n";
+        write(&project.path().join(&path), STOCK);
+        write(&stage.path().join(&path), STOCK);
+        let mut previous = Manifest::default();
+        previous
+            .record(project.path(), "sourcemod", &project.path().join(&path))
+            .unwrap();
+        fs::write(project.path().join(&path), live).unwrap();
+        previous.record_edited(project.path(), &path).unwrap();
+        let recorded = previous.find(&path).unwrap();
+        assert_eq!(
+            recorded.hash,
+            fsutil::hash_file(project.path().join(&path)).unwrap()
+        );
+        assert_ne!(recorded.stock_hash(), recorded.hash);
+
+        let results = reconcile_configs(
+            project.path(),
+            stage.path(),
+            std::slice::from_ref(&path),
+            &previous,
+            &HashMap::new(),
+            &[],
+        )
+        .unwrap();
+        assert_eq!(results, vec![(path.clone(), ConfigUpgrade::Skipped)]);
+        assert_eq!(fs::read_to_string(stage.path().join(&path)).unwrap(), live);
+    }
+
+    #[test]
+    fn merge3_reports_conflicts() {
+        let base = b"a\nb\nc\n";
+        assert_eq!(
+            merge3(base, b"A\nb\nc\n", b"a\nb\nC\n").as_deref(),
+            Some(&b"A\nb\nC\n"[..])
+        );
+        assert_eq!(
+            merge3(base, b"A\nb\nc\n", b"A\nb\nc\n").as_deref(),
+            Some(&b"A\nb\nc\n"[..])
+        );
+        assert_eq!(merge3(base, b"x\nb\nc\n", b"y\nb\nc\n"), None);
+    }
+}
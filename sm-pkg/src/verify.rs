@@ -0,0 +1,116 @@
+use crate::{BoxResult, fsutil, manifest::Manifest};
+use std::{
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+};
+
+/// Directories, relative to the mod folder, that are scanned for files sm-pkg did not install.
+const UNTRACKED_DIRS: &[&str] = &["addons/sourcemod/plugins", "addons/sourcemod/extensions"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DriftKind {
+    /// The file exists but its contents differ from what was installed or generated.
+    Modified,
+    /// The file was installed or generated but no longer exists.
+    Missing,
+    /// The file exists but was not installed by sm-pkg.
+    Untracked,
+}
+
+impl Display for DriftKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DriftKind::Modified => write!(f, "modified"),
+            DriftKind::Missing => write!(f, "missing"),
+            DriftKind::Untracked => write!(f, "untracked"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Drift {
+    pub kind: DriftKind,
+    /// Path relative to the project root.
+    pub path: PathBuf,
+    /// Plugin that owns the file, or None for generated and untracked files.
+    pub owner: Option<String>,
+}
+
+/// Compare the game directory with the install manifest, returning everything that has drifted.
+pub fn verify(
+    project_root: &Path,
+    mod_folder: &Path,
+    manifest: &Manifest,
+) -> BoxResult<Vec<Drift>> {
+    let mut drift = Vec::new();
+
+    // Generated configs replace the stock file recorded for the runtime, only the generated
+    // version is checked.
+    let tracked = manifest
+        .files
+        .iter()
+        .filter(|f| manifest.find_generated(&f.path).is_none())
+        .map(|f| (&f.path, &f.hash, Some(&f.plugin)))
+        .chain(manifest.generated.iter().map(|f| (&f.path, &f.hash, None)));
+    for (path, hash, owner) in tracked {
+        let full_path = project_root.join(path);
+        let kind = if !full_path.exists() {
+            DriftKind::Missing
+        } else if &fsutil::hash_file(&full_path)? != hash {
+            DriftKind::Modified
+        } else {
+            continue;
+        };
+        drift.push(Drift {
+            kind,
+            path: path.clone(),
+            owner: owner.cloned(),
+        });
+    }
+
+    for dir in UNTRACKED_DIRS {
+        let root = project_root.join(mod_folder).join(dir);
+        if !root.exists() {
+            continue;
+        }
+        for rel in fsutil::list_files(&root)? {
+            let path = mod_folder.join(dir).join(rel);
+            if manifest.find(&path).is_none() && manifest.find_generated(&path).is_none() {
+                drift.push(Drift {
+                    kind: DriftKind::Untracked,
+                    path,
+                    owner: None,
+                });
+            }
+        }
+    }
+
+    Ok(drift)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::write;
+    use std::fs;
+
+    #[test]
+    fn generated_configs_are_not_reported_against_the_stock_file() {
+        let root = tempfile::tempdir().unwrap();
+        let config = root.path().join("cstrike/cfg/sourcemod/sourcemod.cfg");
+        write(&config, "stock\n");
+        let mut manifest = Manifest::default();
+        manifest.record(root.path(), "sourcemod", &config).unwrap();
+        fs::write(&config, "generated\n").unwrap();
+        manifest.record_generated(root.path(), &config).unwrap();
+
+        let drift = verify(root.path(), Path::new("cstrike"), &manifest).unwrap();
+        assert!(drift.is_empty(), "{:?}", drift);
+
+        fs::write(&config, "edited\n").unwrap();
+        let drift = verify(root.path(), Path::new("cstrike"), &manifest).unwrap();
+        assert_eq!(drift.len(), 1);
+        assert_eq!(drift[0].kind, DriftKind::Modified);
+        assert_eq!(drift[0].owner, None);
+    }
+}